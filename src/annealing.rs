@@ -0,0 +1,178 @@
+//! Control how sigma2 changes from one iteration to the next.
+
+/// Sigma2 annealing strategies.
+///
+/// By default, sigma2 is whatever each iteration's M-step estimates. Annealing replaces or bounds
+/// that estimate with a user-defined schedule, which can give more robust convergence on noisy
+/// inputs. All sigma2 values are in normalized units, the same as `Runner::sigma2` and
+/// `Run::sigma2`. Multiply by the square of the fixed points' normalization scale to get the units
+/// of the input points.
+///
+/// The default annealing strategy is `None`:
+///
+/// ```
+/// use cpd::Annealing;
+/// assert_eq!(Annealing::None, Annealing::default());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Annealing {
+    /// Use the M-step's estimate of sigma2.
+    None,
+
+    /// Pin sigma2 to a fixed value.
+    Fixed(f64),
+
+    /// Decay sigma2 geometrically, from an initial value to a floor.
+    Geometric {
+        /// The initial sigma2.
+        ///
+        /// If none, use the runner's initial sigma2.
+        initial: Option<f64>,
+
+        /// The factor that the scheduled sigma2 is multiplied by after every iteration.
+        factor: f64,
+
+        /// The smallest scheduled sigma2.
+        floor: f64,
+
+        /// If true, use the larger of the scheduled sigma2 and the M-step's estimate.
+        ///
+        /// The schedule then acts as a decaying lower bound on the estimate.
+        combine: bool,
+    },
+}
+
+/// The state of an annealing schedule during a run, in normalized units.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Schedule {
+    annealing: Annealing,
+    scheduled: Option<f64>,
+}
+
+impl Default for Annealing {
+    fn default() -> Annealing {
+        Annealing::None
+    }
+}
+
+impl Schedule {
+    /// Creates a new schedule.
+    pub(crate) fn new(annealing: Annealing) -> Schedule {
+        Schedule {
+            annealing: annealing,
+            scheduled: None,
+        }
+    }
+
+    /// Returns the sigma2 to start a level with, given the default estimate for that level.
+    pub(crate) fn start(&mut self, estimate: f64) -> f64 {
+        match self.annealing {
+            Annealing::None => estimate,
+            Annealing::Fixed(sigma2) => sigma2,
+            Annealing::Geometric {
+                initial, combine, ..
+            } => {
+                let scheduled = self.scheduled
+                    .or(initial)
+                    .unwrap_or(estimate);
+                self.scheduled = Some(scheduled);
+                if combine {
+                    scheduled.max(estimate)
+                } else {
+                    scheduled
+                }
+            }
+        }
+    }
+
+    /// Returns the sigma2 to use after an iteration, given the M-step's estimate.
+    pub(crate) fn step(&mut self, estimate: f64) -> f64 {
+        match self.annealing {
+            Annealing::None => estimate,
+            Annealing::Fixed(sigma2) => sigma2,
+            Annealing::Geometric {
+                factor,
+                floor,
+                combine,
+                ..
+            } => {
+                let scheduled = self.scheduled.map_or(estimate, |scheduled| {
+                    (scheduled * factor).max(floor)
+                });
+                self.scheduled = Some(scheduled);
+                if combine {
+                    scheduled.max(estimate)
+                } else {
+                    scheduled
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Normalize, Runner, utils};
+
+    #[test]
+    fn geometric() {
+        let annealing = Annealing::Geometric {
+            initial: Some(8.),
+            factor: 0.5,
+            floor: 1.,
+            combine: false,
+        };
+        let mut schedule = Schedule::new(annealing);
+        assert_eq!(8., schedule.start(100.));
+        assert_eq!(4., schedule.step(100.));
+        assert_eq!(2., schedule.step(100.));
+        assert_eq!(1., schedule.step(100.));
+        assert_eq!(1., schedule.step(100.));
+    }
+
+    #[test]
+    fn geometric_combine() {
+        let annealing = Annealing::Geometric {
+            initial: None,
+            factor: 0.5,
+            floor: 0.,
+            combine: true,
+        };
+        let mut schedule = Schedule::new(annealing);
+        assert_eq!(4., schedule.start(4.));
+        assert_eq!(3., schedule.step(3.));
+        assert_eq!(1., schedule.step(0.1));
+    }
+
+    #[test]
+    fn fixed() {
+        let fixed = utils::random_matrix2(10);
+        let moving = utils::random_matrix2(10);
+        let run = Runner::new()
+            .normalize(Normalize::None)
+            .annealing(Annealing::Fixed(0.5))
+            .max_iterations(5)
+            .rigid()
+            .register(&fixed, &moving)
+            .unwrap();
+        assert_eq!(0.5, run.sigma2);
+        assert_eq!(0.5, run.denormalized_sigma2);
+    }
+
+    #[test]
+    fn fixed_is_normalized() {
+        let fixed = utils::random_matrix2(10) * 10.;
+        let moving = utils::random_matrix2(10) * 10.;
+        let run = Runner::new()
+            .normalize(Normalize::SameScale)
+            .annealing(Annealing::Fixed(0.5))
+            .max_iterations(5)
+            .rigid()
+            .register(&fixed, &moving)
+            .unwrap();
+        assert_eq!(0.5, run.sigma2);
+        assert!(run.denormalized_sigma2 != 0.5);
+    }
+}
@@ -0,0 +1,386 @@
+//! Read and write matrices as delimited text, e.g. csv, tsv, or xyz files.
+//!
+//! ```
+//! use cpd::U3;
+//! use cpd::delimited::{DelimitedReader, DelimitedWriter};
+//! let matrix = DelimitedReader::new().read_path::<U3, _>("tests/data/face.csv").unwrap();
+//! let mut buffer = Vec::new();
+//! DelimitedWriter::new().delimiter(' ').write(&mut buffer, &matrix).unwrap();
+//! let xyz = DelimitedReader::new().whitespace().read::<U3, _>(&buffer[..]).unwrap();
+//! assert_eq!(matrix, xyz);
+//! ```
+
+use Matrix;
+use failure::Error;
+use nalgebra::DimName;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// An error returned if a line of delimited text does not have a requested column.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Line {} does not have column {}", line, column)]
+pub struct MissingColumn {
+    /// The one-based line number.
+    pub line: usize,
+    /// The zero-based column index.
+    pub column: usize,
+}
+
+/// An error returned if a value in delimited text is not a number.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Line {} has a value that is not a number: {}", line, value)]
+pub struct InvalidValue {
+    /// The one-based line number.
+    pub line: usize,
+    /// The value that could not be parsed.
+    pub value: String,
+}
+
+/// An error returned if the number of selected columns does not match the dimension.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Selected {} columns for a {}-dimensional matrix", columns, dimension)]
+pub struct WrongNumberOfColumns {
+    /// The number of selected columns.
+    pub columns: usize,
+    /// The dimension of the matrix.
+    pub dimension: usize,
+}
+
+/// How a `DelimitedReader` separates values on a line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delimiter {
+    /// A single character, e.g. a comma or a tab.
+    Char(char),
+
+    /// Any amount of whitespace, e.g. for xyz files.
+    Whitespace,
+}
+
+/// Reads matrices from delimited text, e.g. csv files.
+///
+/// By default, values are separated by commas, no lines are skipped, and the first `D` columns
+/// are read. Empty lines are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::U2;
+/// use cpd::delimited::DelimitedReader;
+/// let fish = DelimitedReader::new().read_path::<U2, _>("tests/data/fish.csv").unwrap();
+/// assert_eq!(91, fish.nrows());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DelimitedReader {
+    columns: Option<Vec<usize>>,
+    delimiter: Delimiter,
+    skip_lines: usize,
+}
+
+/// Writes matrices as delimited text, e.g. csv files.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::utils;
+/// use cpd::delimited::DelimitedWriter;
+/// let matrix = utils::matrix2_from_slice(&[1., 2., 3., 4.]);
+/// let mut buffer = Vec::new();
+/// DelimitedWriter::new().header(&["x", "y"]).write(&mut buffer, &matrix).unwrap();
+/// assert_eq!("x,y\n1,3\n2,4\n", String::from_utf8(buffer).unwrap());
+/// ```
+#[derive(Clone, Debug)]
+pub struct DelimitedWriter {
+    delimiter: char,
+    header: Option<Vec<String>>,
+}
+
+impl DelimitedReader {
+    /// Creates a new delimited text reader.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedReader;
+    /// let reader = DelimitedReader::new();
+    /// ```
+    pub fn new() -> DelimitedReader {
+        DelimitedReader::default()
+    }
+
+    /// Sets the delimiter between values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedReader;
+    /// let reader = DelimitedReader::new().delimiter(';');
+    /// ```
+    pub fn delimiter(mut self, delimiter: char) -> DelimitedReader {
+        self.delimiter = Delimiter::Char(delimiter);
+        self
+    }
+
+    /// Separates values by any amount of whitespace, e.g. for xyz files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedReader;
+    /// let reader = DelimitedReader::new().whitespace();
+    /// ```
+    pub fn whitespace(mut self) -> DelimitedReader {
+        self.delimiter = Delimiter::Whitespace;
+        self
+    }
+
+    /// Sets the number of lines to skip at the start of the text, e.g. a header line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedReader;
+    /// let reader = DelimitedReader::new().skip_lines(1);
+    /// ```
+    pub fn skip_lines(mut self, skip_lines: usize) -> DelimitedReader {
+        self.skip_lines = skip_lines;
+        self
+    }
+
+    /// Sets the zero-based columns to read, one per dimension.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::delimited::DelimitedReader;
+    /// let text = "id,x,y\n1,2.,3.\n";
+    /// let matrix = DelimitedReader::new()
+    ///     .skip_lines(1)
+    ///     .columns(&[1, 2])
+    ///     .read::<U2, _>(text.as_bytes())
+    ///     .unwrap();
+    /// assert_eq!(3., matrix[(0, 1)]);
+    /// ```
+    pub fn columns(mut self, columns: &[usize]) -> DelimitedReader {
+        self.columns = Some(columns.to_vec());
+        self
+    }
+
+    /// Reads a matrix from delimited text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::delimited::DelimitedReader;
+    /// let text = "1 2 3\n4 5 6\n";
+    /// let matrix = DelimitedReader::new().whitespace().read::<U3, _>(text.as_bytes()).unwrap();
+    /// assert_eq!(2, matrix.nrows());
+    /// ```
+    pub fn read<D, R>(&self, read: R) -> Result<Matrix<D>, Error>
+    where
+        D: DimName,
+        R: Read,
+    {
+        let columns = self.columns
+            .clone()
+            .unwrap_or_else(|| (0..D::dim()).collect());
+        if columns.len() != D::dim() {
+            return Err(WrongNumberOfColumns {
+                columns: columns.len(),
+                dimension: D::dim(),
+            }.into());
+        }
+        let mut data = Vec::new();
+        for (i, line) in BufReader::new(read).lines().enumerate().skip(self.skip_lines) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values = match self.delimiter {
+                Delimiter::Char(delimiter) => line.split(delimiter).collect::<Vec<_>>(),
+                Delimiter::Whitespace => line.split_whitespace().collect::<Vec<_>>(),
+            };
+            for &column in &columns {
+                let value = values
+                    .get(column)
+                    .ok_or(MissingColumn {
+                        line: i + 1,
+                        column: column,
+                    })?
+                    .trim();
+                data.push(value.parse::<f64>().map_err(|_| InvalidValue {
+                    line: i + 1,
+                    value: value.to_string(),
+                })?);
+            }
+        }
+        Ok(Matrix::<D>::from_row_slice(&data))
+    }
+
+    /// Reads a matrix from a delimited text file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::delimited::DelimitedReader;
+    /// let face = DelimitedReader::new().read_path::<U3, _>("tests/data/face.csv").unwrap();
+    /// assert_eq!(392, face.nrows());
+    /// ```
+    pub fn read_path<D, P>(&self, path: P) -> Result<Matrix<D>, Error>
+    where
+        D: DimName,
+        P: AsRef<Path>,
+    {
+        self.read(File::open(path)?)
+    }
+}
+
+impl Default for Delimiter {
+    fn default() -> Delimiter {
+        Delimiter::Char(',')
+    }
+}
+
+impl DelimitedWriter {
+    /// Creates a new delimited text writer, which separates values with commas and writes no
+    /// header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedWriter;
+    /// let writer = DelimitedWriter::new();
+    /// ```
+    pub fn new() -> DelimitedWriter {
+        DelimitedWriter {
+            delimiter: ',',
+            header: None,
+        }
+    }
+
+    /// Sets the delimiter between values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedWriter;
+    /// let writer = DelimitedWriter::new().delimiter(' ');
+    /// ```
+    pub fn delimiter(mut self, delimiter: char) -> DelimitedWriter {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the column names to write as a header line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::delimited::DelimitedWriter;
+    /// let writer = DelimitedWriter::new().header(&["x", "y", "z"]);
+    /// ```
+    pub fn header(mut self, header: &[&str]) -> DelimitedWriter {
+        self.header = Some(header.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Writes a matrix as delimited text, one row per line.
+    ///
+    /// Values are written with full precision, so they read back exactly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::utils;
+/// use cpd::delimited::DelimitedWriter;
+    /// let matrix = utils::random_matrix2(10);
+    /// let mut buffer = Vec::new();
+    /// DelimitedWriter::new().write(&mut buffer, &matrix).unwrap();
+    /// ```
+    pub fn write<D, W>(&self, mut write: W, matrix: &Matrix<D>) -> Result<(), Error>
+    where
+        D: DimName,
+        W: Write,
+    {
+        let delimiter = self.delimiter.to_string();
+        if let Some(ref header) = self.header {
+            writeln!(write, "{}", header.join(&delimiter))?;
+        }
+        for row in matrix.row_iter() {
+            let values = row.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+            writeln!(write, "{}", values.join(&delimiter))?;
+        }
+        Ok(())
+    }
+
+    /// Writes a matrix to a delimited text file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::utils;
+/// use cpd::delimited::DelimitedWriter;
+    /// let matrix = utils::random_matrix2(10);
+    /// let path = std::env::temp_dir().join("cpd-delimited-writer.csv");
+    /// DelimitedWriter::new().write_path(&path, &matrix).unwrap();
+    /// ```
+    pub fn write_path<D, P>(&self, path: P, matrix: &Matrix<D>) -> Result<(), Error>
+    where
+        D: DimName,
+        P: AsRef<Path>,
+    {
+        let mut write = BufWriter::new(File::create(path)?);
+        self.write(&mut write, matrix)?;
+        write.flush()?;
+        Ok(())
+    }
+}
+
+impl Default for DelimitedWriter {
+    fn default() -> DelimitedWriter {
+        DelimitedWriter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::U3;
+
+    #[test]
+    fn delimited_round_trip() {
+        let face = DelimitedReader::new()
+            .read_path::<U3, _>("tests/data/face.csv")
+            .unwrap();
+        let mut buffer = Vec::new();
+        DelimitedWriter::new()
+            .delimiter('\t')
+            .header(&["x", "y", "z"])
+            .write(&mut buffer, &face)
+            .unwrap();
+        let other = DelimitedReader::new()
+            .delimiter('\t')
+            .skip_lines(1)
+            .read::<U3, _>(&buffer[..])
+            .unwrap();
+        assert_eq!(face, other);
+    }
+
+    #[test]
+    fn delimited_errors() {
+        let reader = DelimitedReader::new();
+        assert!(reader.read::<U3, _>("1,2\n".as_bytes()).is_err());
+        assert!(reader.read::<U3, _>("1,2,x\n".as_bytes()).is_err());
+        assert!(reader.columns(&[0]).read::<U3, _>("1,2,3\n".as_bytes()).is_err());
+    }
+    #[test]
+    fn space_is_not_whitespace() {
+        let text = "1  2 3\n".as_bytes();
+        let matrix = DelimitedReader::new().whitespace().read::<U3, _>(text).unwrap();
+        assert_eq!(3., matrix[(0, 2)]);
+        assert!(DelimitedReader::new().delimiter(' ').read::<U3, _>(text).is_err());
+    }
+}
@@ -0,0 +1,800 @@
+//! Read, write, and transform las files.
+//!
+//! Requires the `las` feature, which is enabled by default.
+
+use {Matrix, Vector};
+use failure::Error;
+use las_rs::{Builder, Header, Point, Reader, Vlr, Writer};
+use nalgebra::{DMatrix, U3, Vector3};
+use rigid::Transform;
+use std::f64;
+use std::path::Path;
+
+/// An error returned if a las attribute is requested from a file whose point format lacks it.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "The las point format does not have {:?}", _0)]
+pub struct MissingLasAttribute(pub LasAttribute);
+
+/// Point attributes that `LasReader` can return alongside the point positions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LasAttribute {
+    /// The intensity, one column.
+    Intensity,
+
+    /// The GPS time, one column.
+    GpsTime,
+
+    /// The red, green, and blue channels, three columns.
+    Color,
+}
+
+/// Points read by a `LasReader`.
+#[derive(Debug)]
+pub struct LasPoints {
+    /// The requested attributes, one row per point and one or more columns per attribute, in the
+    /// order they were requested. `None` if no attributes were requested.
+    pub attributes: Option<DMatrix<f64>>,
+
+    /// The point positions.
+    pub points: Matrix<U3>,
+}
+
+/// Reads las points into matrices, with optional filtering, decimation, and attributes.
+///
+/// Points are streamed and filtered as they are read, so only the kept points are stored. All
+/// filters must pass for a point to be kept.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::las::{LasAttribute, LasReader};
+/// let las = LasReader::new()
+///     .return_numbers(&[1])
+///     .stride(2)
+///     .attribute(LasAttribute::Intensity)
+///     .read_path("tests/data/face.las")
+///     .unwrap();
+/// assert_eq!(las.points.nrows(), las.attributes.unwrap().nrows());
+/// ```
+#[derive(Clone, Debug)]
+pub struct LasReader {
+    attributes: Vec<LasAttribute>,
+    bounds: Option<(Vector<U3>, Vector<U3>)>,
+    classifications: Option<Vec<u8>>,
+    max_scan_angle: Option<f32>,
+    return_numbers: Option<Vec<u8>>,
+    stride: usize,
+}
+
+impl LasReader {
+    /// Creates a new las reader that keeps every point and reads no attributes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasReader;
+    /// let reader = LasReader::new();
+    /// ```
+    pub fn new() -> LasReader {
+        LasReader {
+            attributes: Vec::new(),
+            bounds: None,
+            classifications: None,
+            max_scan_angle: None,
+            return_numbers: None,
+            stride: 1,
+        }
+    }
+
+    /// Only keeps points with one of these classifications.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasReader;
+    /// let ground = LasReader::new().classifications(&[2]);
+    /// ```
+    pub fn classifications(mut self, classifications: &[u8]) -> LasReader {
+        self.classifications = Some(classifications.to_vec());
+        self
+    }
+
+    /// Only keeps points with one of these return numbers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasReader;
+    /// let first_returns = LasReader::new().return_numbers(&[1]);
+    /// ```
+    pub fn return_numbers(mut self, return_numbers: &[u8]) -> LasReader {
+        self.return_numbers = Some(return_numbers.to_vec());
+        self
+    }
+
+    /// Only keeps points inside these bounds, inclusive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{U3, Vector};
+    /// use cpd::las::LasReader;
+    /// let min = Vector::<U3>::new(0., 0., 0.);
+    /// let max = Vector::<U3>::new(10., 10., 10.);
+    /// let reader = LasReader::new().bounds(min, max);
+    /// ```
+    pub fn bounds(
+        mut self,
+        min: Vector<U3>,
+        max: Vector<U3>,
+    ) -> LasReader {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// Only keeps points whose absolute scan angle, in degrees, is at most this value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasReader;
+    /// let near_nadir = LasReader::new().max_scan_angle(15.);
+    /// ```
+    pub fn max_scan_angle(mut self, max_scan_angle: f32) -> LasReader {
+        self.max_scan_angle = Some(max_scan_angle);
+        self
+    }
+
+    /// Keeps every `stride`th point of those that pass the filters.
+    ///
+    /// A stride of zero is treated as one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasReader;
+    /// let every_tenth = LasReader::new().stride(10);
+    /// ```
+    pub fn stride(mut self, stride: usize) -> LasReader {
+        self.stride = stride.max(1);
+        self
+    }
+
+    /// Adds an attribute to the side matrix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::{LasAttribute, LasReader};
+    /// let reader = LasReader::new()
+    ///     .attribute(LasAttribute::GpsTime)
+    ///     .attribute(LasAttribute::Color);
+    /// ```
+    pub fn attribute(mut self, attribute: LasAttribute) -> LasReader {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// Reads the las file at a path.
+    ///
+    /// Returns an error if an attribute is requested that the file's point format does not have.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasReader;
+    /// let las = LasReader::new().read_path("tests/data/face.las").unwrap();
+    /// assert_eq!(392, las.points.nrows());
+    /// assert!(las.attributes.is_none());
+    /// ```
+    pub fn read_path<P>(&self, path: P) -> Result<LasPoints, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut reader = Reader::from_path(path)?;
+        let format = reader.header().point_format();
+        for &attribute in &self.attributes {
+            let present = match attribute {
+                LasAttribute::Intensity => true,
+                LasAttribute::GpsTime => format.has_gps_time,
+                LasAttribute::Color => format.has_color,
+            };
+            if !present {
+                return Err(MissingLasAttribute(attribute).into());
+            }
+        }
+        let mut points = Vec::new();
+        let mut attributes = Vec::new();
+        let mut passed = 0;
+        for point in reader.points() {
+            let point = point?;
+            if !self.keeps(&point) {
+                continue;
+            }
+            passed += 1;
+            if (passed - 1) % self.stride != 0 {
+                continue;
+            }
+            points.extend_from_slice(&[point.x, point.y, point.z]);
+            for attribute in &self.attributes {
+                match *attribute {
+                    LasAttribute::Intensity => attributes.push(f64::from(point.intensity)),
+                    LasAttribute::GpsTime => attributes.push(point.gps_time.unwrap_or(0.)),
+                    LasAttribute::Color => {
+                        let color = point.color.unwrap_or_default();
+                        attributes.push(f64::from(color.red));
+                        attributes.push(f64::from(color.green));
+                        attributes.push(f64::from(color.blue));
+                    }
+                }
+            }
+        }
+        let nrows = points.len() / 3;
+        let ncols = self.attributes
+            .iter()
+            .map(|&attribute| if attribute == LasAttribute::Color { 3 } else { 1 })
+            .sum();
+        Ok(LasPoints {
+            attributes: if self.attributes.is_empty() {
+                None
+            } else {
+                Some(DMatrix::from_row_slice(nrows, ncols, &attributes))
+            },
+            points: Matrix::<U3>::from_row_slice(&points),
+        })
+    }
+
+    fn keeps(&self, point: &Point) -> bool {
+        if let Some(ref classifications) = self.classifications {
+            if !classifications.contains(&u8::from(point.classification)) {
+                return false;
+            }
+        }
+        if let Some(ref return_numbers) = self.return_numbers {
+            if !return_numbers.contains(&point.return_number) {
+                return false;
+            }
+        }
+        if let Some((ref min, ref max)) = self.bounds {
+            let xyz = [point.x, point.y, point.z];
+            if (0..3).any(|i| xyz[i] < min[i] || xyz[i] > max[i]) {
+                return false;
+            }
+        }
+        if let Some(max_scan_angle) = self.max_scan_angle {
+            if point.scan_angle.abs() > max_scan_angle {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Default for LasReader {
+    fn default() -> LasReader {
+        LasReader::new()
+    }
+}
+
+/// Applies a rigid transform to every point in a las file, writing the result to a new las file.
+///
+/// Points are streamed, so the file does not have to fit in memory. All other point attributes,
+/// the variable length records, and the header's scale factors are kept. The header's offsets are
+/// moved to the center of the transformed bounds, so the transformed points keep their precision,
+/// and the bounds are recalculated from the transformed points.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{U3, las, utils};
+/// use cpd::rigid::Transform;
+/// let mut transform = Transform::<U3>::identity();
+/// transform.translation[0] = 1000.;
+/// let path = std::env::temp_dir().join("cpd-transform-las-path.las");
+/// las::transform_las_path("tests/data/face.las", &path, &transform).unwrap();
+/// let moved = utils::matrix_from_las_path(&path).unwrap();
+/// let face = utils::matrix_from_las_path("tests/data/face.las").unwrap();
+/// assert!((moved[(0, 0)] - face[(0, 0)] - 1000.).abs() < 1e-6);
+/// ```
+pub fn transform_las_path<P, Q>(input: P, output: Q, transform: &Transform<U3>) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let apply = |x: f64, y: f64, z: f64| {
+        transform.scale.unwrap_or(1.) * transform.rotation * Vector3::new(x, y, z)
+            + transform.translation
+    };
+    let mut reader = Reader::from_path(input)?;
+    let bounds = reader.header().bounds();
+    let mut min = Vector3::repeat(f64::INFINITY);
+    let mut max = Vector3::repeat(f64::NEG_INFINITY);
+    for &x in &[bounds.min.x, bounds.max.x] {
+        for &y in &[bounds.min.y, bounds.max.y] {
+            for &z in &[bounds.min.z, bounds.max.z] {
+                let corner = apply(x, y, z);
+                min = min.zip_map(&corner, f64::min);
+                max = max.zip_map(&corner, f64::max);
+            }
+        }
+    }
+    let center = (min + max) / 2.;
+    let mut builder = Builder::from(reader.header().clone());
+    recenter(&mut builder.transforms.x, center.x);
+    recenter(&mut builder.transforms.y, center.y);
+    recenter(&mut builder.transforms.z, center.z);
+    let format = builder.point_format;
+    let mut writer = Writer::from_path(output, builder.into_header()?)?;
+    for point in reader.points() {
+        let mut point = point?;
+        let moved = apply(point.x, point.y, point.z);
+        point.x = moved.x;
+        point.y = moved.y;
+        point.z = moved.z;
+        // The las reader reads a zero gps time as no gps time, which the writer then refuses.
+        if format.has_gps_time && point.gps_time.is_none() {
+            point.gps_time = Some(0.);
+        }
+        writer.write(point)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+fn recenter(transform: &mut ::las_rs::Transform, center: f64) {
+    if center.is_finite() {
+        transform.offset = (center / transform.scale).round() * transform.scale;
+    }
+}
+
+/// An error returned if a las attribute does not have one value per point.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Attribute {} has {} values, but there are {} points", name, values, points)]
+pub struct AttributeLengthMismatch {
+    /// The attribute name.
+    pub name: String,
+    /// The number of attribute values.
+    pub values: usize,
+    /// The number of points.
+    pub points: usize,
+}
+
+/// An error returned if a las attribute name does not fit in an extra bytes descriptor.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Attribute name {} is longer than 32 bytes", _0)]
+pub struct AttributeNameTooLong(pub String);
+
+/// Writes three-dimensional matrices, e.g. `Run::moved`, to las files.
+///
+/// Per-point attributes, e.g. membership probabilities or displacement magnitudes, are written as
+/// double-precision extra bytes, described by a LASF_Spec extra bytes (record id 4) variable length
+/// record so that other software can read them by name.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::las::LasWriter;
+/// use cpd::utils;
+/// let face = utils::matrix_from_las_path("tests/data/face.las").unwrap();
+/// let distances = (0..face.nrows()).map(|i| face.row(i).norm()).collect::<Vec<_>>();
+/// let path = std::env::temp_dir().join("cpd-las-writer.las");
+/// LasWriter::new()
+///     .scale(0.001, 0.001, 0.001)
+///     .attribute("distance", "Distance from the origin", &distances)
+///     .write(&path, &face)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct LasWriter {
+    attributes: Vec<(String, String, Vec<f64>)>,
+    offset: Option<[f64; 3]>,
+    scale: [f64; 3],
+    vlrs: Vec<Vlr>,
+}
+
+impl LasWriter {
+    /// Creates a new las writer.
+    ///
+    /// The default scale is 0.001 in every dimension, and the default offset is the center of the
+    /// points' bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasWriter;
+    /// let writer = LasWriter::new();
+    /// ```
+    pub fn new() -> LasWriter {
+        LasWriter {
+            attributes: Vec::new(),
+            offset: None,
+            scale: [0.001; 3],
+            vlrs: Vec::new(),
+        }
+    }
+
+    /// Sets the header scale factors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasWriter;
+    /// let writer = LasWriter::new().scale(0.01, 0.01, 0.001);
+    /// ```
+    pub fn scale(mut self, x: f64, y: f64, z: f64) -> LasWriter {
+        self.scale = [x, y, z];
+        self
+    }
+
+    /// Sets the header offsets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasWriter;
+    /// let writer = LasWriter::new().offset(400000., 4000000., 0.);
+    /// ```
+    pub fn offset(mut self, x: f64, y: f64, z: f64) -> LasWriter {
+        self.offset = Some([x, y, z]);
+        self
+    }
+
+    /// Takes the scale factors, offsets, and variable length records (e.g. the coordinate
+    /// reference system) from another header, such as the header of the file that the points were
+    /// read from.
+    ///
+    /// The source's extra bytes descriptions are not copied, since they describe the source's
+    /// points.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate cpd;
+    /// extern crate las;
+    /// # fn main() {
+    /// use cpd::las::LasWriter;
+    /// use las::Reader;
+    /// let reader = Reader::from_path("tests/data/face.las").unwrap();
+    /// let writer = LasWriter::new().header(reader.header());
+    /// # }
+    /// ```
+    pub fn header(mut self, header: &Header) -> LasWriter {
+        let transforms = header.transforms();
+        self.scale = [transforms.x.scale, transforms.y.scale, transforms.z.scale];
+        self.offset = Some([transforms.x.offset, transforms.y.offset, transforms.z.offset]);
+        self.vlrs = header
+            .vlrs()
+            .iter()
+            .filter(|vlr| !is_extra_bytes(vlr))
+            .cloned()
+            .collect();
+        self
+    }
+
+    /// Adds a per-point attribute, written as double-precision extra bytes.
+    ///
+    /// The name can be at most 32 bytes long, and there must be one value per point. Both are
+    /// checked when writing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasWriter;
+    /// let writer = LasWriter::new().attribute("probability", "Membership probability", &[0.5]);
+    /// ```
+    pub fn attribute(mut self, name: &str, description: &str, values: &[f64]) -> LasWriter {
+        self.attributes
+            .push((name.to_string(), description.to_string(), values.to_vec()));
+        self
+    }
+
+    /// Writes the points and their attributes to a las file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::las::LasWriter;
+/// use cpd::utils;
+    /// let face = utils::matrix_from_las_path("tests/data/face.las").unwrap();
+    /// let path = std::env::temp_dir().join("cpd-las-writer-write.las");
+    /// LasWriter::new().write(&path, &face).unwrap();
+    /// ```
+    pub fn write<P>(&self, path: P, matrix: &Matrix<U3>) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        use las_rs::{Point, Transform, Version};
+
+        for &(ref name, _, ref values) in &self.attributes {
+            if name.len() > 32 {
+                return Err(AttributeNameTooLong(name.clone()).into());
+            }
+            if values.len() != matrix.nrows() {
+                return Err(AttributeLengthMismatch {
+                    name: name.clone(),
+                    values: values.len(),
+                    points: matrix.nrows(),
+                }.into());
+            }
+        }
+        let offset = self.offset.unwrap_or_else(|| {
+            let mut offset = [0.; 3];
+            for (d, offset) in offset.iter_mut().enumerate() {
+                if matrix.nrows() > 0 {
+                    let column = matrix.column(d);
+                    let center = (column.iter().cloned().fold(f64::INFINITY, f64::min)
+                        + column.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+                        / 2.;
+                    *offset = (center / self.scale[d]).round() * self.scale[d];
+                }
+            }
+            offset
+        });
+        let mut builder = if self.attributes.is_empty() {
+            Builder::default()
+        } else {
+            Builder::from(Version::new(1, 4))
+        };
+        builder.transforms.x = Transform {
+            scale: self.scale[0],
+            offset: offset[0],
+        };
+        builder.transforms.y = Transform {
+            scale: self.scale[1],
+            offset: offset[1],
+        };
+        builder.transforms.z = Transform {
+            scale: self.scale[2],
+            offset: offset[2],
+        };
+        builder.point_format.extra_bytes = (8 * self.attributes.len()) as u16;
+        builder.vlrs = self.vlrs.clone();
+        if !self.attributes.is_empty() {
+            let mut data = Vec::with_capacity(192 * self.attributes.len());
+            for &(ref name, ref description, ref values) in &self.attributes {
+                let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                let start = data.len();
+                // Reserved, data type (double), and options (min and max are set).
+                data.extend_from_slice(&[0, 0, 10, 0b110]);
+                data.extend(padded(name, 32));
+                // Unused, then no data.
+                data.extend_from_slice(&[0; 4 + 24]);
+                // Min and max, each the first of three values.
+                data.extend_from_slice(&min.to_bits().to_le_bytes());
+                data.extend_from_slice(&[0; 16]);
+                data.extend_from_slice(&max.to_bits().to_le_bytes());
+                data.extend_from_slice(&[0; 16]);
+                // Scale and offset.
+                data.extend_from_slice(&[0; 24 + 24]);
+                data.extend(padded(description, 32));
+                debug_assert_eq!(192, data.len() - start);
+            }
+            builder.vlrs.push(Vlr {
+                user_id: "LASF_Spec".to_string(),
+                record_id: 4,
+                description: "Extra bytes".to_string(),
+                data: data,
+            });
+        }
+        let mut writer = Writer::from_path(path, builder.into_header()?)?;
+        for i in 0..matrix.nrows() {
+            let mut point = Point {
+                x: matrix[(i, 0)],
+                y: matrix[(i, 1)],
+                z: matrix[(i, 2)],
+                ..Default::default()
+            };
+            for &(_, _, ref values) in &self.attributes {
+                point.extra_bytes.extend_from_slice(&values[i].to_bits().to_le_bytes());
+            }
+            writer.write(point)?;
+        }
+        writer.close()?;
+        Ok(())
+    }
+}
+
+impl Default for LasWriter {
+    fn default() -> LasWriter {
+        LasWriter::new()
+    }
+}
+
+fn is_extra_bytes(vlr: &Vlr) -> bool {
+    vlr.user_id == "LASF_Spec" && vlr.record_id == 4
+}
+
+fn padded(s: &str, len: usize) -> Vec<u8> {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.resize(len, 0);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use las_rs::point::{Classification, Format};
+    use std::f64::consts::FRAC_PI_2;
+
+    fn point(x: f64, y: f64, z: f64, i: u8) -> Point {
+        Point {
+            x: x,
+            y: y,
+            z: z,
+            intensity: 100 * i as u16,
+            return_number: i,
+            number_of_returns: 3,
+            classification: Classification::new(i).unwrap(),
+            gps_time: Some(10. * i as f64),
+            ..Default::default()
+        }
+    }
+
+    fn write(path: &Path, points: &[Point]) {
+        let mut builder = Builder::default();
+        builder.point_format = Format::new(1).unwrap();
+        let mut writer = Writer::from_path(path, builder.into_header().unwrap()).unwrap();
+        for point in points {
+            writer.write(point.clone()).unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn transform_keeps_attributes() {
+        let input = ::std::env::temp_dir().join("cpd-las-transform-input.las");
+        let output = ::std::env::temp_dir().join("cpd-las-transform-output.las");
+        let points = vec![
+            point(1., 2., 3., 1),
+            point(4., 5., 6., 2),
+            point(-7., 8., -9., 3),
+        ];
+        write(&input, &points);
+
+        let transform = Transform {
+            translation: Vector::<U3>::new(1000., 2000., 3000.),
+            ..Transform::from_yaw_pitch_roll(FRAC_PI_2, 0., 0.)
+        };
+        transform_las_path(&input, &output, &transform).unwrap();
+
+        let mut reader = Reader::from_path(&output).unwrap();
+        let header = reader.header().clone();
+        let moved = reader.points().map(|point| point.unwrap()).collect::<Vec<_>>();
+        assert_eq!(points.len(), moved.len());
+        let mut min = Vector3::repeat(f64::INFINITY);
+        let mut max = Vector3::repeat(f64::NEG_INFINITY);
+        for (point, moved) in points.iter().zip(&moved) {
+            let expected = transform.rotation * Vector3::new(point.x, point.y, point.z)
+                + transform.translation;
+            min = min.zip_map(&expected, f64::min);
+            max = max.zip_map(&expected, f64::max);
+            assert_relative_eq!(expected.x, moved.x, epsilon = 1e-3);
+            assert_relative_eq!(expected.y, moved.y, epsilon = 1e-3);
+            assert_relative_eq!(expected.z, moved.z, epsilon = 1e-3);
+            assert_eq!(point.intensity, moved.intensity);
+            assert_eq!(point.return_number, moved.return_number);
+            assert_eq!(point.number_of_returns, moved.number_of_returns);
+            assert_eq!(point.classification, moved.classification);
+            assert_eq!(point.gps_time, moved.gps_time);
+        }
+
+        let bounds = header.bounds();
+        assert_relative_eq!(min.x, bounds.min.x, epsilon = 1e-3);
+        assert_relative_eq!(min.y, bounds.min.y, epsilon = 1e-3);
+        assert_relative_eq!(min.z, bounds.min.z, epsilon = 1e-3);
+        assert_relative_eq!(max.x, bounds.max.x, epsilon = 1e-3);
+        assert_relative_eq!(max.y, bounds.max.y, epsilon = 1e-3);
+        assert_relative_eq!(max.z, bounds.max.z, epsilon = 1e-3);
+        let center = (min + max) / 2.;
+        let transforms = header.transforms();
+        assert_relative_eq!(center.x, transforms.x.offset, epsilon = transforms.x.scale);
+        assert_relative_eq!(center.y, transforms.y.offset, epsilon = transforms.y.scale);
+        assert_relative_eq!(center.z, transforms.z.offset, epsilon = transforms.z.scale);
+    }
+    #[test]
+    fn las_writer_extra_bytes() {
+        let points = Matrix::<U3>::from_row_slice(&[1., 2., 3., 4., 5., 6.]);
+        let path = ::std::env::temp_dir().join("cpd-las-writer-extra-bytes.las");
+        LasWriter::new()
+            .attribute("a", "First", &[0.25, -1.])
+            .attribute("b", "Second", &[3., 4.5])
+            .write(&path, &points)
+            .unwrap();
+        let mut reader = Reader::from_path(&path).unwrap();
+        let data = reader
+            .header()
+            .vlrs()
+            .iter()
+            .find(|vlr| is_extra_bytes(vlr))
+            .unwrap()
+            .data
+            .clone();
+        assert_eq!(192 * 2, data.len());
+        assert_eq!(b"b", &data[192 + 4..192 + 5]);
+        assert_eq!(b"Second", &data[192 + 160..192 + 166]);
+        let values = reader
+            .points()
+            .map(|point| {
+                point
+                    .unwrap()
+                    .extra_bytes
+                    .chunks(8)
+                    .map(|chunk| {
+                        let bits = (0..8).fold(0u64, |bits, i| bits | (chunk[i] as u64) << (8 * i));
+                        f64::from_bits(bits)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![vec![0.25, 3.], vec![-1., 4.5]], values);
+    }
+
+    #[test]
+    fn las_reader() {
+        let path = "tests/data/face.las";
+        let all = LasReader::new()
+            .attribute(LasAttribute::Color)
+            .attribute(LasAttribute::Intensity)
+            .read_path(path)
+            .unwrap();
+        assert_eq!((392, 4), all.attributes.unwrap().shape());
+        let default = LasReader::default().read_path(path).unwrap();
+        assert_eq!(all.points, default.points);
+        let strided = LasReader::new().stride(10).read_path(path).unwrap();
+        assert_eq!(40, strided.points.nrows());
+        assert_eq!(all.points.row(10), strided.points.row(1));
+        let min = all.points.row(0).transpose();
+        let bounded = LasReader::new()
+            .bounds(min, min)
+            .read_path(path)
+            .unwrap();
+        assert!(bounded.points.nrows() >= 1);
+        let none = LasReader::new()
+            .classifications(&[])
+            .read_path(path)
+            .unwrap();
+        assert_eq!(0, none.points.nrows());
+    }
+    #[test]
+    fn las_reader_filters() {
+        let path = ::std::env::temp_dir().join("cpd-las-reader-filters.las");
+        let points = [(1, 5., 2, 10.), (1, -20., 3, 20.), (2, -10., 5, 30.), (3, 30., 2, 40.)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(return_number, scan_angle, classification, gps_time))| Point {
+                x: i as f64,
+                return_number: return_number,
+                number_of_returns: 3,
+                scan_angle: scan_angle,
+                classification: Classification::new(classification).unwrap(),
+                gps_time: Some(gps_time),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        write(&path, &points);
+        let kept = |reader: LasReader| {
+            let las = reader.read_path(&path).unwrap();
+            las.points.column(0).iter().cloned().collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![0., 1.], kept(LasReader::new().return_numbers(&[1])));
+        assert_eq!(vec![2., 3.], kept(LasReader::new().return_numbers(&[2, 3])));
+        assert_eq!(vec![0., 2.], kept(LasReader::new().max_scan_angle(10.)));
+        assert_eq!(vec![0., 1., 2.], kept(LasReader::new().max_scan_angle(20.)));
+        assert_eq!(vec![0., 3.], kept(LasReader::new().classifications(&[2])));
+        assert_eq!(vec![1., 2.], kept(LasReader::new().classifications(&[3, 5])));
+        assert_eq!(vec![0.], kept(LasReader::new().return_numbers(&[1]).classifications(&[2])));
+
+        let las = LasReader::new()
+            .classifications(&[2, 5])
+            .attribute(LasAttribute::GpsTime)
+            .read_path(&path)
+            .unwrap();
+        assert_eq!(vec![0., 2., 3.], las.points.column(0).iter().cloned().collect::<Vec<_>>());
+        assert_eq!(
+            vec![10., 30., 40.],
+            las.attributes.unwrap().column(0).iter().cloned().collect::<Vec<_>>()
+        );
+    }
+}
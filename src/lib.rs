@@ -41,6 +41,17 @@
 //! let rigid = Rigid::new();
 //! let run = rigid.register(&fixed, &moving).unwrap();
 //! ```
+//!
+//! # Features
+//!
+//! - **las** (default): read and write [las](https://crates.io/crates/las) files.
+//! - **pcd**: read and write point clouds in the Point Cloud Library's pcd format.
+//! - **ply**: read and write point clouds in the polygon file format (ply).
+//! - **rayon**: register batches of point sets in parallel with
+//! [rayon](https://crates.io/crates/rayon).
+//! - **serde**: serialize and deserialize configurations (e.g. `Runner` and `Rigid`) and results
+//! (e.g. `Run` and `rigid::Transform`) with [serde](https://serde.rs/), and read and write
+//! transforms as JSON.
 
 #![deny(missing_docs, missing_debug_implementations, missing_copy_implementations, trivial_casts,
         trivial_numeric_casts, unsafe_code, unstable_features, unused_import_braces,
@@ -55,23 +66,45 @@ extern crate failure;
 extern crate failure_derive;
 extern crate generic_array;
 #[cfg(feature = "las")]
-extern crate las;
+extern crate las as las_rs;
 #[macro_use]
 extern crate log;
 extern crate nalgebra;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
+pub mod annealing;
+pub mod delimited;
 pub mod gauss_transform;
+#[cfg(feature = "las")]
+pub mod las;
 pub mod normalize;
+#[cfg(feature = "pcd")]
+pub mod pcd;
+#[cfg(feature = "ply")]
+pub mod ply;
+pub mod pose_graph;
 pub mod rigid;
 pub mod runner;
 pub mod utils;
+pub mod validate;
 mod registration;
+mod transform;
 
+pub use annealing::Annealing;
 pub use nalgebra::{U2, U3};
 pub use normalize::{Normalization, Normalize};
 pub use registration::Registration;
 pub use rigid::Rigid;
 pub use runner::{Run, Runner};
+pub use transform::PointTransform;
 
 /// Our custom dynamic-row matrix type.
 pub type Matrix<D> = nalgebra::MatrixMN<f64, nalgebra::Dynamic, D>;
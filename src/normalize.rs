@@ -18,6 +18,7 @@ use std::ops::Mul;
 /// assert_eq!(Normalize::SameScale, Normalize::default());
 /// ```
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Normalize {
     /// Normalize both point sets independently.
     ///
@@ -40,7 +41,11 @@ pub enum Normalize {
 ///
 /// Normalization is a scale and offset that are used to transform points to (roughly) a unit
 /// volume.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde",
+           serde(bound(serialize = "Parameters<D>: ::serde::Serialize",
+                       deserialize = "Parameters<D>: ::serde::Deserialize<'de>")))]
 pub struct Normalization<D: DimName>
 /**
 where
@@ -57,7 +62,11 @@ where
 }
 
 /// Normalization parameters.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde",
+           serde(bound(serialize = "Vector<D>: ::serde::Serialize",
+                       deserialize = "Vector<D>: ::serde::Deserialize<'de>")))]
 pub struct Parameters<D>
 where
     D: DimName,
@@ -136,6 +145,46 @@ impl Normalize {
         Cow<'a, Matrix<D>>,
         Option<Normalization<D>>,
     )
+    where
+        D: DimName,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
+        match *self {
+            Normalize::None => (Cow::Borrowed(fixed), Cow::Borrowed(moving), None),
+            _ => self.normalize_with(fixed, &Parameters::new(fixed), moving),
+        }
+    }
+
+    /// Normalizes two matrices, reusing already-calculated parameters for the fixed matrix.
+    ///
+    /// Useful when normalizing many moving matrices against the same fixed matrix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Normalize, utils};
+    /// use cpd::normalize::Parameters;
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let parameters = Parameters::new(&fixed);
+    /// let (fixed2, moving2, normalization) = Normalize::SameScale.normalize_with(
+    ///     &fixed,
+    ///     &parameters,
+    ///     &moving,
+    /// );
+    /// assert_eq!(Normalize::SameScale.normalize(&fixed, &moving).2, normalization);
+    /// ```
+    pub fn normalize_with<'a, D>(
+        &self,
+        fixed: &'a Matrix<D>,
+        fixed_parameters: &Parameters<D>,
+        moving: &'a Matrix<D>,
+    ) -> (
+        Cow<'a, Matrix<D>>,
+        Cow<'a, Matrix<D>>,
+        Option<Normalization<D>>,
+    )
     where
         D: DimName,
         <D as DimName>::Value: Mul + Mul<UInt>,
@@ -143,14 +192,14 @@ impl Normalize {
     {
         match *self {
             Normalize::Independent => {
-                let normalization = Normalization::new(fixed, moving);
+                let normalization = Normalization::new(fixed_parameters, moving);
                 let mut fixed = fixed.clone();
                 let mut moving = moving.clone();
                 normalization.normalize(&mut fixed, &mut moving);
                 (Cow::Owned(fixed), Cow::Owned(moving), Some(normalization))
             }
             Normalize::SameScale => {
-                let mut normalization = Normalization::new(fixed, moving);
+                let mut normalization = Normalization::new(fixed_parameters, moving);
                 normalization.set_scales_to_mean();
                 let mut fixed = fixed.clone();
                 let mut moving = moving.clone();
@@ -160,6 +209,37 @@ impl Normalize {
             Normalize::None => (Cow::Borrowed(fixed), Cow::Borrowed(moving), None),
         }
     }
+
+    /// Like `normalize_with`, but for a fixed matrix that has already been normalized with its own
+    /// parameters, so it can be shared between many moving matrices.
+    pub(crate) fn normalize_prepared<'a, D>(
+        &self,
+        fixed: &'a Matrix<D>,
+        fixed_parameters: &Parameters<D>,
+        moving: &'a Matrix<D>,
+    ) -> (
+        Cow<'a, Matrix<D>>,
+        Cow<'a, Matrix<D>>,
+        Option<Normalization<D>>,
+    )
+    where
+        D: DimName,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
+        let mut normalization = Normalization::new(fixed_parameters, moving);
+        let fixed = match *self {
+            Normalize::Independent => Cow::Borrowed(fixed),
+            Normalize::SameScale => {
+                normalization.set_scales_to_mean();
+                Cow::Owned(fixed * (fixed_parameters.scale / normalization.fixed.scale))
+            }
+            Normalize::None => return (Cow::Borrowed(fixed), Cow::Borrowed(moving), None),
+        };
+        let mut moving = moving.clone();
+        normalization.moving.normalize(&mut moving);
+        (fixed, Cow::Owned(moving), Some(normalization))
+    }
 }
 
 impl Default for Normalize {
@@ -174,9 +254,9 @@ where
     <D as DimName>::Value: Mul + Mul<UInt>,
     <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
 {
-    fn new(fixed: &Matrix<D>, moving: &Matrix<D>) -> Normalization<D> {
+    fn new(fixed: &Parameters<D>, moving: &Matrix<D>) -> Normalization<D> {
         Normalization {
-            fixed: Parameters::new(fixed),
+            fixed: fixed.clone(),
             moving: Parameters::new(moving),
         }
     }
@@ -0,0 +1,718 @@
+//! Read and write point clouds in the Point Cloud Library's pcd format.
+//!
+//! Requires the `pcd` feature. The `ascii`, `binary`, and `binary_compressed` data formats are
+//! supported. Binary values are little endian, as written by PCL on all common platforms.
+//!
+//! ```
+//! use cpd::{U3, pcd};
+//! use cpd::delimited::DelimitedReader;
+//! use cpd::pcd::Data;
+//! let matrix = DelimitedReader::new().read_path::<U3, _>("tests/data/face.csv").unwrap();
+//! let mut buffer = Vec::new();
+//! pcd::Writer::new()
+//!     .data(Data::BinaryCompressed)
+//!     .write(&mut buffer, &matrix)
+//!     .unwrap();
+//! let pcd = pcd::read(&buffer[..]).unwrap();
+//! assert_eq!(matrix, pcd.points);
+//! ```
+
+use Matrix;
+use failure::Error;
+use nalgebra::{DVector, U3};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const AXES: [&str; 3] = ["x", "y", "z"];
+
+/// The largest back-reference distance of an lzf match.
+const LZF_MAX_OFFSET: usize = 1 << 13;
+
+/// The longest lzf match.
+const LZF_MAX_MATCH: usize = 264;
+
+/// The longest run of lzf literals.
+const LZF_MAX_LITERALS: usize = 32;
+
+/// An error returned if a pcd header is malformed or unsupported.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Invalid pcd header: {}", _0)]
+pub struct InvalidHeader(pub String);
+
+/// An error returned if the pcd data does not match its header.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Invalid pcd data: {}", _0)]
+pub struct InvalidData(pub String);
+
+/// An error returned if a pcd file is missing one of the `x`, `y`, and `z` fields.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "The pcd file has no {} field", _0)]
+pub struct MissingField(pub String);
+
+/// An error returned if an extra field does not have one value per point.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Field {} has {} values, but there are {} points", name, values, points)]
+pub struct FieldLengthMismatch {
+    /// The field name.
+    pub name: String,
+    /// The number of field values.
+    pub values: usize,
+    /// The number of points.
+    pub points: usize,
+}
+
+/// The pcd data formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Data {
+    /// Whitespace-separated text, one point per line.
+    Ascii,
+
+    /// Little endian binary, one point after another.
+    Binary,
+
+    /// Lzf-compressed little endian binary, one field after another.
+    BinaryCompressed,
+}
+
+/// The points of a pcd file.
+#[derive(Debug)]
+pub struct Pcd {
+    /// All other fields, by name, in header order.
+    ///
+    /// Fields with a count greater than one are split into one entry per element, named e.g.
+    /// `normal_0`, `normal_1`, and so on. Padding fields named `_` are dropped. Packed fields,
+    /// such as PCL's `rgb`, are returned as their stored numeric value.
+    pub fields: Vec<(String, DVector<f64>)>,
+
+    /// The point positions (`x`, `y`, and `z`).
+    pub points: Matrix<U3>,
+}
+
+/// Writes point matrices as pcd files, with optional extra fields.
+///
+/// All values are written as doubles, so they read back exactly. The default data format is
+/// binary.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{pcd, utils};
+/// let matrix = utils::random_matrix3(10);
+/// let mut buffer = Vec::new();
+/// pcd::Writer::new()
+///     .field("probability", &[0.5; 10])
+///     .write(&mut buffer, &matrix)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Writer {
+    data: Data,
+    fields: Vec<(String, Vec<f64>)>,
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+    count: usize,
+    kind: char,
+    name: String,
+    size: usize,
+}
+
+#[derive(Debug)]
+struct Header {
+    data: Data,
+    fields: Vec<Field>,
+    points: usize,
+}
+
+/// Reads the points of a pcd file.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::pcd;
+/// let text = "VERSION 0.7\nFIELDS x y z intensity\nSIZE 4 4 4 4\nTYPE F F F F\nCOUNT 1 1 1 1\n\
+///             WIDTH 2\nHEIGHT 1\nPOINTS 2\nDATA ascii\n1 2 3 0.5\n4 5 6 0.7\n";
+/// let pcd = pcd::read(text.as_bytes()).unwrap();
+/// assert_eq!(4., pcd.points[(1, 0)]);
+/// assert_eq!("intensity", pcd.fields[0].0);
+/// ```
+pub fn read<R: Read>(read: R) -> Result<Pcd, Error> {
+    let mut read = BufReader::new(read);
+    let header = read_header(&mut read)?;
+    let slots = header.fields.iter().map(|field| field.count).sum::<usize>();
+    let mut columns = vec![Vec::new(); slots];
+    match header.data {
+        Data::Ascii => {
+            let mut body = String::new();
+            read.read_to_string(&mut body)?;
+            let mut tokens = body.split_whitespace();
+            for _ in 0..header.points {
+                for column in &mut columns {
+                    let token = tokens
+                        .next()
+                        .ok_or_else(|| InvalidData("unexpected end of data".to_string()))?;
+                    column.push(parse(token)?);
+                }
+            }
+        }
+        Data::Binary => {
+            let record = header.fields.iter().map(Field::bytes).sum::<usize>();
+            let mut bytes = vec![0; record];
+            for _ in 0..header.points {
+                read.read_exact(&mut bytes)?;
+                let mut offset = 0;
+                let mut slot = 0;
+                for field in &header.fields {
+                    for _ in 0..field.count {
+                        columns[slot].push(field.value(&bytes[offset..offset + field.size]));
+                        offset += field.size;
+                        slot += 1;
+                    }
+                }
+            }
+        }
+        Data::BinaryCompressed => {
+            let record = header.fields.iter().map(Field::bytes).sum::<usize>();
+            let expected = record
+                .checked_mul(header.points)
+                .ok_or_else(|| InvalidHeader("too many points".to_string()))?;
+            let mut sizes = [0; 8];
+            read.read_exact(&mut sizes)?;
+            let compressed = u32::from_le_bytes([sizes[0], sizes[1], sizes[2], sizes[3]]);
+            let compressed = compressed as usize;
+            let uncompressed = u32::from_le_bytes([sizes[4], sizes[5], sizes[6], sizes[7]]);
+            let uncompressed = uncompressed as usize;
+            // Check the sizes before allocating, so that a corrupt header can't exhaust memory. Lzf
+            // adds at most one control byte per run of literals.
+            if uncompressed != expected {
+                return Err(InvalidData(format!(
+                    "expected {} uncompressed bytes, got {}",
+                    expected, uncompressed
+                )).into());
+            }
+            if compressed > expected + expected / LZF_MAX_LITERALS + 1 {
+                return Err(InvalidData(format!(
+                    "{} compressed bytes is too many for {} uncompressed bytes",
+                    compressed, expected
+                )).into());
+            }
+            let mut bytes = vec![0; compressed];
+            read.read_exact(&mut bytes)?;
+            let bytes = decompress(&bytes, expected)?;
+            let mut offset = 0;
+            let mut slot = 0;
+            for field in &header.fields {
+                for i in 0..header.points {
+                    for k in 0..field.count {
+                        let start = offset + (i * field.count + k) * field.size;
+                        columns[slot + k].push(field.value(&bytes[start..start + field.size]));
+                    }
+                }
+                offset += field.bytes() * header.points;
+                slot += field.count;
+            }
+        }
+    }
+    let mut names = Vec::with_capacity(slots);
+    for field in &header.fields {
+        for k in 0..field.count {
+            names.push(if field.name == "_" {
+                None
+            } else if field.count == 1 {
+                Some(field.name.clone())
+            } else {
+                Some(format!("{}_{}", field.name, k))
+            });
+        }
+    }
+    let mut axes = Vec::with_capacity(AXES.len());
+    for axis in &AXES {
+        axes.push(
+            names
+                .iter()
+                .position(|name| name.as_ref().map(String::as_str) == Some(*axis))
+                .ok_or_else(|| MissingField(axis.to_string()))?,
+        );
+    }
+    let points = Matrix::<U3>::from_fn(header.points, |i, j| columns[axes[j]][i]);
+    let fields = names
+        .into_iter()
+        .zip(columns)
+        .filter_map(|(name, column)| name.map(|name| (name, column)))
+        .filter(|&(ref name, _)| !AXES.contains(&name.as_str()))
+        .map(|(name, column)| (name, DVector::from_vec(column)))
+        .collect();
+    Ok(Pcd {
+        fields: fields,
+        points: points,
+    })
+}
+
+/// Reads the points of a pcd file at a path.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{pcd, utils};
+/// let matrix = utils::random_matrix3(10);
+/// let path = std::env::temp_dir().join("cpd-pcd-read-path.pcd");
+/// pcd::Writer::new().write_path(&path, &matrix).unwrap();
+/// let pcd = pcd::read_path(&path).unwrap();
+/// assert_eq!(matrix, pcd.points);
+/// ```
+pub fn read_path<P: AsRef<Path>>(path: P) -> Result<Pcd, Error> {
+    read(File::open(path)?)
+}
+
+impl Writer {
+    /// Creates a new pcd writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::pcd::Writer;
+    /// let writer = Writer::new();
+    /// ```
+    pub fn new() -> Writer {
+        Writer {
+            data: Data::Binary,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Sets the data format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::pcd::{Data, Writer};
+    /// let writer = Writer::new().data(Data::Ascii);
+    /// ```
+    pub fn data(mut self, data: Data) -> Writer {
+        self.data = data;
+        self
+    }
+
+    /// Adds an extra per-point field.
+    ///
+    /// There must be one value per point, which is checked when writing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::pcd::Writer;
+    /// let writer = Writer::new().field("distance", &[1., 2., 3.]);
+    /// ```
+    pub fn field(mut self, name: &str, values: &[f64]) -> Writer {
+        self.fields.push((name.to_string(), values.to_vec()));
+        self
+    }
+
+    /// Writes a matrix as a pcd file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{pcd, utils};
+    /// let matrix = utils::random_matrix3(10);
+    /// let mut buffer = Vec::new();
+    /// pcd::Writer::new().write(&mut buffer, &matrix).unwrap();
+    /// ```
+    pub fn write<W: Write>(&self, mut write: W, matrix: &Matrix<U3>) -> Result<(), Error> {
+        let points = matrix.nrows();
+        for &(ref name, ref values) in &self.fields {
+            if values.len() != points {
+                return Err(FieldLengthMismatch {
+                    name: name.clone(),
+                    values: values.len(),
+                    points: points,
+                }.into());
+            }
+        }
+        let mut columns = matrix
+            .column_iter()
+            .map(|column| column.iter().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        columns.extend(self.fields.iter().map(|&(_, ref values)| values.clone()));
+        let names = AXES.iter()
+            .map(|axis| axis.to_string())
+            .chain(self.fields.iter().map(|&(ref name, _)| name.clone()))
+            .collect::<Vec<_>>();
+        let repeat = |value: &str| vec![value; names.len()].join(" ");
+        writeln!(write, "# .PCD v0.7 - Point Cloud Data file format")?;
+        writeln!(write, "VERSION 0.7")?;
+        writeln!(write, "FIELDS {}", names.join(" "))?;
+        writeln!(write, "SIZE {}", repeat("8"))?;
+        writeln!(write, "TYPE {}", repeat("F"))?;
+        writeln!(write, "COUNT {}", repeat("1"))?;
+        writeln!(write, "WIDTH {}", points)?;
+        writeln!(write, "HEIGHT 1")?;
+        writeln!(write, "VIEWPOINT 0 0 0 1 0 0 0")?;
+        writeln!(write, "POINTS {}", points)?;
+        match self.data {
+            Data::Ascii => {
+                writeln!(write, "DATA ascii")?;
+                for i in 0..points {
+                    let row = columns
+                        .iter()
+                        .map(|column| column[i].to_string())
+                        .collect::<Vec<_>>();
+                    writeln!(write, "{}", row.join(" "))?;
+                }
+            }
+            Data::Binary => {
+                writeln!(write, "DATA binary")?;
+                for i in 0..points {
+                    for column in &columns {
+                        write.write_all(&column[i].to_bits().to_le_bytes())?;
+                    }
+                }
+            }
+            Data::BinaryCompressed => {
+                writeln!(write, "DATA binary_compressed")?;
+                let bytes = columns
+                    .iter()
+                    .flat_map(|column| column.iter())
+                    .flat_map(|n| n.to_bits().to_le_bytes().to_vec())
+                    .collect::<Vec<_>>();
+                let compressed = compress(&bytes);
+                write.write_all(&(compressed.len() as u32).to_le_bytes())?;
+                write.write_all(&(bytes.len() as u32).to_le_bytes())?;
+                write.write_all(&compressed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a matrix as a pcd file at a path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{pcd, utils};
+    /// let matrix = utils::random_matrix3(10);
+    /// let path = std::env::temp_dir().join("cpd-pcd-write-path.pcd");
+    /// pcd::Writer::new().write_path(&path, &matrix).unwrap();
+    /// ```
+    pub fn write_path<P: AsRef<Path>>(&self, path: P, matrix: &Matrix<U3>) -> Result<(), Error> {
+        let mut write = BufWriter::new(File::create(path)?);
+        self.write(&mut write, matrix)?;
+        write.flush()?;
+        Ok(())
+    }
+}
+
+impl Default for Writer {
+    fn default() -> Writer {
+        Writer::new()
+    }
+}
+
+impl Field {
+    fn bytes(&self) -> usize {
+        self.size * self.count
+    }
+
+    fn value(&self, bytes: &[u8]) -> f64 {
+        let mut b = [0; 8];
+        b[..bytes.len()].copy_from_slice(bytes);
+        match (self.kind, self.size) {
+            ('I', 1) => f64::from(b[0] as i8),
+            ('I', 2) => f64::from(i16::from_le_bytes([b[0], b[1]])),
+            ('I', 4) => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            ('I', _) => i64::from_le_bytes(b) as f64,
+            ('U', 1) => f64::from(b[0]),
+            ('U', 2) => f64::from(u16::from_le_bytes([b[0], b[1]])),
+            ('U', 4) => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            ('U', _) => u64::from_le_bytes(b) as f64,
+            ('F', 4) => f64::from(f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))),
+            _ => f64::from_bits(u64::from_le_bytes(b)),
+        }
+    }
+}
+
+fn read_header<R: BufRead>(read: &mut R) -> Result<Header, Error> {
+    let mut names = Vec::new();
+    let mut sizes = Vec::new();
+    let mut kinds = Vec::new();
+    let mut counts = Vec::new();
+    let mut width = None;
+    let mut height = None;
+    let mut points = None;
+    let data;
+    loop {
+        let mut line = String::new();
+        if read.read_line(&mut line)? == 0 {
+            return Err(InvalidHeader("no DATA line".to_string()).into());
+        }
+        let mut words = line.split_whitespace();
+        let key = match words.next() {
+            Some(key) if !key.starts_with('#') => key.to_uppercase(),
+            _ => continue,
+        };
+        let values = words.collect::<Vec<_>>();
+        let number = || -> Result<usize, InvalidHeader> {
+            values
+                .get(0)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| InvalidHeader(format!("invalid {} line", key)))
+        };
+        match key.as_str() {
+            "VERSION" | "VIEWPOINT" => {}
+            "FIELDS" => names = values.iter().map(|name| name.to_string()).collect(),
+            "SIZE" => sizes = parse_list(&values, &key)?,
+            "TYPE" => kinds = values.iter().map(|kind| kind.to_uppercase()).collect(),
+            "COUNT" => counts = parse_list(&values, &key)?,
+            "WIDTH" => width = Some(number()?),
+            "HEIGHT" => height = Some(number()?),
+            "POINTS" => points = Some(number()?),
+            "DATA" => {
+                data = match values.get(0).map(|value| value.to_lowercase()) {
+                    Some(ref value) if value == "ascii" => Data::Ascii,
+                    Some(ref value) if value == "binary" => Data::Binary,
+                    Some(ref value) if value == "binary_compressed" => Data::BinaryCompressed,
+                    _ => return Err(InvalidHeader(format!("unsupported {}", line.trim())).into()),
+                };
+                break;
+            }
+            _ => return Err(InvalidHeader(format!("unexpected line: {}", line.trim())).into()),
+        }
+    }
+    if counts.is_empty() {
+        counts = vec![1; names.len()];
+    }
+    if sizes.len() != names.len() || kinds.len() != names.len() || counts.len() != names.len() {
+        let message = "FIELDS, SIZE, TYPE, and COUNT lengths differ".to_string();
+        return Err(InvalidHeader(message).into());
+    }
+    let mut fields = Vec::with_capacity(names.len());
+    for (((name, size), kind), count) in names.into_iter().zip(sizes).zip(kinds).zip(counts) {
+        let valid = match kind.as_str() {
+            "I" | "U" => size == 1 || size == 2 || size == 4 || size == 8,
+            "F" => size == 4 || size == 8,
+            _ => false,
+        };
+        if !valid {
+            return Err(InvalidHeader(format!("unsupported type {}{} for {}", kind, size, name))
+                .into());
+        }
+        fields.push(Field {
+            count: count,
+            kind: kind.chars().next().unwrap_or('F'),
+            name: name,
+            size: size,
+        });
+    }
+    let points = match (points, width, height) {
+        (Some(points), _, _) => points,
+        (None, Some(width), Some(height)) => width * height,
+        _ => return Err(InvalidHeader("no POINTS, WIDTH, or HEIGHT".to_string()).into()),
+    };
+    Ok(Header {
+        data: data,
+        fields: fields,
+        points: points,
+    })
+}
+
+fn parse_list(values: &[&str], key: &str) -> Result<Vec<usize>, InvalidHeader> {
+    values
+        .iter()
+        .map(|value| value.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| InvalidHeader(format!("invalid {} line", key)))
+}
+
+fn parse(token: &str) -> Result<f64, InvalidData> {
+    if token.eq_ignore_ascii_case("nan") {
+        Ok(::std::f64::NAN)
+    } else {
+        token
+            .parse()
+            .map_err(|_| InvalidData(format!("{} is not a number", token)))
+    }
+}
+
+/// Decompresses lzf data, as used by `binary_compressed` pcd files.
+fn decompress(input: &[u8], length: usize) -> Result<Vec<u8>, InvalidData> {
+    let corrupt = || InvalidData("corrupt lzf data".to_string());
+    let mut output = Vec::with_capacity(length);
+    let mut i = 0;
+    while i < input.len() {
+        let control = input[i] as usize;
+        i += 1;
+        if control < LZF_MAX_LITERALS {
+            let end = i + control + 1;
+            if output.len() + control + 1 > length {
+                return Err(corrupt());
+            }
+            output.extend_from_slice(input.get(i..end).ok_or_else(corrupt)?);
+            i = end;
+        } else {
+            let mut len = control >> 5;
+            if len == 7 {
+                len += *input.get(i).ok_or_else(corrupt)? as usize;
+                i += 1;
+            }
+            let distance = ((control & 0x1f) << 8) + *input.get(i).ok_or_else(corrupt)? as usize;
+            i += 1;
+            if distance >= output.len() || output.len() + len + 2 > length {
+                return Err(corrupt());
+            }
+            let start = output.len() - distance - 1;
+            for k in 0..len + 2 {
+                let byte = output[start + k];
+                output.push(byte);
+            }
+        }
+    }
+    if output.len() != length {
+        return Err(InvalidData(format!(
+            "expected {} uncompressed bytes, got {}",
+            length,
+            output.len()
+        )));
+    }
+    Ok(output)
+}
+
+/// Compresses data with lzf, using a simple hash of the next three bytes to find matches.
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + input.len() / LZF_MAX_LITERALS + 1);
+    let mut table = vec![None; 1 << 14];
+    let mut literals = Vec::with_capacity(LZF_MAX_LITERALS);
+    let mut i = 0;
+    while i + 2 < input.len() {
+        let hash = ((usize::from(input[i]) << 16
+            | usize::from(input[i + 1]) << 8
+            | usize::from(input[i + 2]))
+            .wrapping_mul(2_654_435_761) >> 6) & ((1 << 14) - 1);
+        let candidate = table[hash];
+        table[hash] = Some(i);
+        let matched = candidate.filter(|&r| {
+            i - r <= LZF_MAX_OFFSET && input[r..r + 3] == input[i..i + 3]
+        });
+        if let Some(r) = matched {
+            let max = LZF_MAX_MATCH.min(input.len() - i);
+            let mut len = 3;
+            while len < max && input[r + len] == input[i + len] {
+                len += 1;
+            }
+            flush(&mut output, &mut literals);
+            let distance = i - r - 1;
+            let code = len - 2;
+            if code < 7 {
+                output.push((code << 5 | distance >> 8) as u8);
+            } else {
+                output.push((7 << 5 | distance >> 8) as u8);
+                output.push((code - 7) as u8);
+            }
+            output.push((distance & 0xff) as u8);
+            i += len;
+        } else {
+            literals.push(input[i]);
+            if literals.len() == LZF_MAX_LITERALS {
+                flush(&mut output, &mut literals);
+            }
+            i += 1;
+        }
+    }
+    for &byte in &input[i..] {
+        literals.push(byte);
+        if literals.len() == LZF_MAX_LITERALS {
+            flush(&mut output, &mut literals);
+        }
+    }
+    flush(&mut output, &mut literals);
+    output
+}
+
+fn flush(output: &mut Vec<u8>, literals: &mut Vec<u8>) {
+    if !literals.is_empty() {
+        output.push((literals.len() - 1) as u8);
+        output.append(literals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils;
+
+    #[test]
+    fn round_trip() {
+        let matrix = utils::random_matrix3(100);
+        let values = (0..100).map(|n| n as f64).collect::<Vec<_>>();
+        for &data in &[Data::Ascii, Data::Binary, Data::BinaryCompressed] {
+            let mut buffer = Vec::new();
+            Writer::new()
+                .data(data)
+                .field("value", &values)
+                .write(&mut buffer, &matrix)
+                .unwrap();
+            let pcd = read(&buffer[..]).unwrap();
+            assert_eq!(matrix, pcd.points);
+            assert_eq!("value", pcd.fields[0].0);
+            assert_eq!(DVector::from_column_slice(&values), pcd.fields[0].1);
+        }
+    }
+
+    #[test]
+    fn lzf() {
+        let input = b"abcabcabcabcabcabcabcabc the quick brown fox abcabcabc jumps 0000000000000000"
+            .iter()
+            .cloned()
+            .cycle()
+            .take(2000)
+            .collect::<Vec<_>>();
+        let compressed = compress(&input);
+        assert!(compressed.len() < input.len());
+        assert_eq!(input, decompress(&compressed, input.len()).unwrap());
+        assert!(decompress(&[0xe0], 10).is_err());
+        assert_eq!(Vec::<u8>::new(), decompress(&compress(&[]), 0).unwrap());
+    }
+
+    #[test]
+    fn binary_types() {
+        let mut buffer = b"VERSION .7\nFIELDS x y z rgb _\nSIZE 4 4 4 4 1\nTYPE F F F U I\n\
+                           COUNT 1 1 1 1 2\nWIDTH 1\nHEIGHT 1\nPOINTS 1\nDATA binary\n"
+            .to_vec();
+        for &n in &[1f32, 2., 3.] {
+            buffer.extend_from_slice(&n.to_bits().to_le_bytes());
+        }
+        buffer.extend_from_slice(&0x00ff_0000u32.to_le_bytes());
+        buffer.extend_from_slice(&[0, 0]);
+        let pcd = read(&buffer[..]).unwrap();
+        assert_eq!(3., pcd.points[(0, 2)]);
+        assert_eq!(1, pcd.fields.len());
+        assert_eq!(f64::from(0x00ff_0000u32), pcd.fields[0].1[0]);
+    }
+
+    #[test]
+    fn compressed_sizes() {
+        let header = b"FIELDS x y z\nSIZE 4 4 4\nTYPE F F F\nPOINTS 2\nDATA binary_compressed\n";
+        let sizes = |compressed: u32, uncompressed: u32| {
+            let mut buffer = header.to_vec();
+            buffer.extend_from_slice(&compressed.to_le_bytes());
+            buffer.extend_from_slice(&uncompressed.to_le_bytes());
+            buffer
+        };
+        let error = read(&sizes(u32::max_value(), 24)[..]).unwrap_err();
+        assert!(error.downcast::<InvalidData>().is_ok());
+        let error = read(&sizes(4, u32::max_value())[..]).unwrap_err();
+        assert!(error.downcast::<InvalidData>().is_ok());
+        let too_long = b"FIELDS x y z\nSIZE 8 8 8\nTYPE F F F\nPOINTS 768614336404564651\n\
+                         DATA binary_compressed\n";
+        let error = read(&too_long[..]).unwrap_err();
+        assert!(error.downcast::<InvalidHeader>().is_ok());
+        assert!(decompress(&compress(&[0; 100]), 10).is_err());
+    }
+
+    #[test]
+    fn missing_field() {
+        let text = "FIELDS x y\nSIZE 4 4\nTYPE F F\nPOINTS 1\nDATA ascii\n1 2\n";
+        assert!(read(text.as_bytes()).is_err());
+    }
+}
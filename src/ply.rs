@@ -0,0 +1,613 @@
+//! Read and write point clouds in the polygon file format (ply).
+//!
+//! Requires the `ply` feature. Ascii, binary little endian, and binary big endian files are
+//! supported. Only the vertex element is read; faces and other elements are skipped.
+//!
+//! ```
+//! use cpd::{U3, ply};
+//! use cpd::delimited::DelimitedReader;
+//! use cpd::ply::Format;
+//! let matrix = DelimitedReader::new().read_path::<U3, _>("tests/data/face.csv").unwrap();
+//! let mut buffer = Vec::new();
+//! ply::Writer::new()
+//!     .format(Format::Ascii)
+//!     .write(&mut buffer, &matrix)
+//!     .unwrap();
+//! let ply = ply::read::<U3, _>(&buffer[..]).unwrap();
+//! assert_eq!(matrix, ply.positions);
+//! ```
+
+use Matrix;
+use failure::Error;
+use nalgebra::{DVector, DimName, U3};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const AXES: [&str; 3] = ["x", "y", "z"];
+const NORMALS: [&str; 3] = ["nx", "ny", "nz"];
+const COLORS: [&str; 3] = ["red", "green", "blue"];
+
+/// An error returned if a ply header is malformed or unsupported.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Invalid ply header: {}", _0)]
+pub struct InvalidHeader(pub String);
+
+/// An error returned if the ply body does not match its header.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Invalid ply data: {}", _0)]
+pub struct InvalidData(pub String);
+
+/// An error returned if the ply vertex element is missing a position property.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "The ply vertex element has no {} property", _0)]
+pub struct MissingProperty(pub String);
+
+/// An error returned if ply positions are requested in more than three dimensions.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Ply positions have at most three dimensions, not {}", _0)]
+pub struct UnsupportedDimension(pub usize);
+
+/// An error returned if a scalar property does not have one value per vertex.
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "Scalar {} has {} values, but there are {} vertices", name, values, vertices)]
+pub struct ScalarLengthMismatch {
+    /// The scalar property name.
+    pub name: String,
+    /// The number of scalar values.
+    pub values: usize,
+    /// The number of vertices.
+    pub vertices: usize,
+}
+
+/// The ply storage formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Whitespace-separated text.
+    Ascii,
+
+    /// Little endian binary.
+    BinaryLittleEndian,
+
+    /// Big endian binary.
+    BinaryBigEndian,
+}
+
+/// The vertices of a ply file.
+#[derive(Debug)]
+pub struct Ply<D>
+where
+    D: DimName,
+{
+    /// The vertex colors (`red`, `green`, and `blue`), if present, with their stored values, e.g.
+    /// zero to 255 for `uchar` colors.
+    pub colors: Option<Matrix<U3>>,
+
+    /// The vertex normals (`nx`, `ny`, and `nz`), if present.
+    pub normals: Option<Matrix<D>>,
+
+    /// The vertex positions (`x`, `y`, and `z`).
+    pub positions: Matrix<D>,
+
+    /// All other scalar vertex properties, by name, in header order.
+    pub scalars: Vec<(String, DVector<f64>)>,
+}
+
+/// Writes point matrices as ply vertices, with optional per-vertex scalar properties.
+///
+/// All values are written as doubles, so they read back exactly. The default format is binary
+/// little endian.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{ply, utils};
+/// let matrix = utils::random_matrix2(10);
+/// let probabilities = vec![0.5; 10];
+/// let mut buffer = Vec::new();
+/// ply::Writer::new()
+///     .scalar("probability", &probabilities)
+///     .write(&mut buffer, &matrix)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Writer {
+    format: Format,
+    scalars: Vec<(String, Vec<f64>)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(Scalar, Scalar),
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    count: usize,
+    name: String,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+struct Values<R> {
+    format: Format,
+    read: R,
+    tokens: ::std::vec::IntoIter<String>,
+}
+
+/// Reads the vertices of a ply file.
+///
+/// Reads `D` position columns, e.g. `x` and `y` for two-dimensional matrices.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{U2, ply};
+/// let text = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+///             end_header\n1 2\n3 4\n";
+/// let ply = ply::read::<U2, _>(text.as_bytes()).unwrap();
+/// assert_eq!(3., ply.positions[(1, 0)]);
+/// ```
+pub fn read<D, R>(read: R) -> Result<Ply<D>, Error>
+where
+    D: DimName,
+    R: Read,
+{
+    let mut read = BufReader::new(read);
+    let (format, elements) = read_header(&mut read)?;
+    let mut tokens = Vec::new();
+    if format == Format::Ascii {
+        let mut body = String::new();
+        read.read_to_string(&mut body)?;
+        tokens = body.split_whitespace().map(String::from).collect();
+    }
+    let mut values = Values {
+        format: format,
+        read: read,
+        tokens: tokens.into_iter(),
+    };
+    for element in &elements {
+        if element.name == "vertex" {
+            return read_vertices(element, &mut values);
+        }
+        for _ in 0..element.count {
+            for property in &element.properties {
+                values.skip(property)?;
+            }
+        }
+    }
+    Err(InvalidHeader("no vertex element".to_string()).into())
+}
+
+/// Reads the vertices of a ply file at a path.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{U2, ply, utils};
+/// let matrix = utils::random_matrix2(10);
+/// let path = std::env::temp_dir().join("cpd-ply-read-path.ply");
+/// ply::Writer::new().write_path(&path, &matrix).unwrap();
+/// let ply = ply::read_path::<U2, _>(&path).unwrap();
+/// assert_eq!(matrix, ply.positions);
+/// ```
+pub fn read_path<D, P>(path: P) -> Result<Ply<D>, Error>
+where
+    D: DimName,
+    P: AsRef<Path>,
+{
+    read(File::open(path)?)
+}
+
+impl Writer {
+    /// Creates a new ply writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::ply::Writer;
+    /// let writer = Writer::new();
+    /// ```
+    pub fn new() -> Writer {
+        Writer {
+            format: Format::BinaryLittleEndian,
+            scalars: Vec::new(),
+        }
+    }
+
+    /// Sets the storage format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::ply::{Format, Writer};
+    /// let writer = Writer::new().format(Format::Ascii);
+    /// ```
+    pub fn format(mut self, format: Format) -> Writer {
+        self.format = format;
+        self
+    }
+
+    /// Adds a per-vertex scalar property.
+    ///
+    /// There must be one value per vertex, which is checked when writing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::ply::Writer;
+    /// let writer = Writer::new().scalar("distance", &[1., 2., 3.]);
+    /// ```
+    pub fn scalar(mut self, name: &str, values: &[f64]) -> Writer {
+        self.scalars.push((name.to_string(), values.to_vec()));
+        self
+    }
+
+    /// Writes a matrix as ply vertices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{ply, utils};
+    /// let matrix = utils::random_matrix2(10);
+    /// let mut buffer = Vec::new();
+    /// ply::Writer::new().write(&mut buffer, &matrix).unwrap();
+    /// ```
+    pub fn write<D, W>(&self, mut write: W, matrix: &Matrix<D>) -> Result<(), Error>
+    where
+        D: DimName,
+        W: Write,
+    {
+        if D::dim() > AXES.len() {
+            return Err(UnsupportedDimension(D::dim()).into());
+        }
+        for &(ref name, ref values) in &self.scalars {
+            if values.len() != matrix.nrows() {
+                return Err(ScalarLengthMismatch {
+                    name: name.clone(),
+                    values: values.len(),
+                    vertices: matrix.nrows(),
+                }.into());
+            }
+        }
+        writeln!(write, "ply")?;
+        writeln!(
+            write,
+            "format {} 1.0",
+            match self.format {
+                Format::Ascii => "ascii",
+                Format::BinaryLittleEndian => "binary_little_endian",
+                Format::BinaryBigEndian => "binary_big_endian",
+            }
+        )?;
+        writeln!(write, "element vertex {}", matrix.nrows())?;
+        for axis in &AXES[..D::dim()] {
+            writeln!(write, "property double {}", axis)?;
+        }
+        for &(ref name, _) in &self.scalars {
+            writeln!(write, "property double {}", name)?;
+        }
+        writeln!(write, "end_header")?;
+        for i in 0..matrix.nrows() {
+            let row = matrix
+                .row(i)
+                .iter()
+                .cloned()
+                .chain(self.scalars.iter().map(|&(_, ref values)| values[i]))
+                .collect::<Vec<_>>();
+            match self.format {
+                Format::Ascii => {
+                    let row = row.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+                    writeln!(write, "{}", row.join(" "))?;
+                }
+                Format::BinaryLittleEndian => for n in row {
+                    write.write_all(&n.to_bits().to_le_bytes())?;
+                },
+                Format::BinaryBigEndian => for n in row {
+                    write.write_all(&n.to_bits().to_be_bytes())?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a matrix as ply vertices to a file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{ply, utils};
+    /// let matrix = utils::random_matrix2(10);
+    /// let path = std::env::temp_dir().join("cpd-ply-write-path.ply");
+    /// ply::Writer::new().write_path(&path, &matrix).unwrap();
+    /// ```
+    pub fn write_path<D, P>(&self, path: P, matrix: &Matrix<D>) -> Result<(), Error>
+    where
+        D: DimName,
+        P: AsRef<Path>,
+    {
+        let mut write = BufWriter::new(File::create(path)?);
+        self.write(&mut write, matrix)?;
+        write.flush()?;
+        Ok(())
+    }
+}
+
+impl Default for Writer {
+    fn default() -> Writer {
+        Writer::new()
+    }
+}
+
+impl Scalar {
+    fn new(name: &str) -> Result<Scalar, InvalidHeader> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(InvalidHeader(format!("unknown property type {}", name))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+impl<R: Read> Values<R> {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, Error> {
+        if self.format == Format::Ascii {
+            let token = self.tokens
+                .next()
+                .ok_or_else(|| InvalidData("unexpected end of data".to_string()))?;
+            return token
+                .parse::<f64>()
+                .map_err(|_| InvalidData(format!("{} is not a number", token)).into());
+        }
+        let mut bytes = [0; 8];
+        let size = scalar.size();
+        self.read.read_exact(&mut bytes[..size])?;
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        let [a, b, c, d, e, f, g, h] = bytes;
+        Ok(match scalar {
+            Scalar::I8 => f64::from(a as i8),
+            Scalar::U8 => f64::from(a),
+            Scalar::I16 => f64::from(i16::from_le_bytes([a, b])),
+            Scalar::U16 => f64::from(u16::from_le_bytes([a, b])),
+            Scalar::I32 => f64::from(i32::from_le_bytes([a, b, c, d])),
+            Scalar::U32 => f64::from(u32::from_le_bytes([a, b, c, d])),
+            Scalar::F32 => f64::from(f32::from_bits(u32::from_le_bytes([a, b, c, d]))),
+            Scalar::F64 => f64::from_bits(u64::from_le_bytes([a, b, c, d, e, f, g, h])),
+        })
+    }
+
+    fn skip(&mut self, property: &Property) -> Result<(), Error> {
+        match *property {
+            Property::Scalar(_, scalar) => {
+                self.next(scalar)?;
+            }
+            Property::List(count, item) => {
+                let count = self.next(count)?;
+                for _ in 0..count as usize {
+                    self.next(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_header<R: BufRead>(read: &mut R) -> Result<(Format, Vec<Element>), Error> {
+    let mut lines = read.lines();
+    let mut next_line = || -> Result<String, Error> {
+        match lines.next() {
+            Some(line) => Ok(line?),
+            None => Err(InvalidHeader("no end_header".to_string()).into()),
+        }
+    };
+    if next_line()?.trim() != "ply" {
+        return Err(InvalidHeader("missing ply magic number".to_string()).into());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let line = next_line()?;
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() || words[0] == "comment" || words[0] == "obj_info" {
+            continue;
+        }
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(InvalidHeader(format!("unknown format {}", name)).into()),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                count: count
+                    .parse()
+                    .map_err(|_| InvalidHeader(format!("invalid count {}", count)))?,
+                name: name.to_string(),
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, _] => elements
+                .last_mut()
+                .ok_or_else(|| InvalidHeader("property before element".to_string()))?
+                .properties
+                .push(Property::List(Scalar::new(count)?, Scalar::new(item)?)),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| InvalidHeader("property before element".to_string()))?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::new(scalar)?)),
+            _ => return Err(InvalidHeader(format!("unexpected line: {}", line)).into()),
+        }
+    }
+    let format = format.ok_or_else(|| InvalidHeader("no format".to_string()))?;
+    Ok((format, elements))
+}
+
+fn read_vertices<D, R>(element: &Element, values: &mut Values<R>) -> Result<Ply<D>, Error>
+where
+    D: DimName,
+    R: Read,
+{
+    if D::dim() > AXES.len() {
+        return Err(UnsupportedDimension(D::dim()).into());
+    }
+    let names = element
+        .properties
+        .iter()
+        .filter_map(|property| match *property {
+            Property::Scalar(ref name, _) => Some(name.as_str()),
+            Property::List(..) => None,
+        })
+        .collect::<Vec<_>>();
+    let mut data = Vec::with_capacity(element.count * names.len());
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match *property {
+                Property::Scalar(_, scalar) => data.push(values.next(scalar)?),
+                Property::List(..) => values.skip(property)?,
+            }
+        }
+    }
+    let column = |name: &str| names.iter().position(|&other| other == name);
+    let gather = |columns: &[usize]| {
+        Matrix::<D>::from_fn(element.count, |i, j| data[i * names.len() + columns[j]])
+    };
+    let positions = AXES[..D::dim()]
+        .iter()
+        .map(|&axis| column(axis).ok_or_else(|| MissingProperty(axis.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let normals = NORMALS[..D::dim()]
+        .iter()
+        .map(|&normal| column(normal))
+        .collect::<Option<Vec<_>>>();
+    let colors = COLORS
+        .iter()
+        .map(|&color| column(color))
+        .collect::<Option<Vec<_>>>();
+    let used = |name: &str| {
+        AXES[..D::dim()].contains(&name)
+            || (normals.is_some() && NORMALS[..D::dim()].contains(&name))
+            || (colors.is_some() && COLORS.contains(&name))
+    };
+    let scalars = names
+        .iter()
+        .enumerate()
+        .filter(|&(_, name)| !used(name))
+        .map(|(j, name)| {
+            let values = (0..element.count).map(|i| data[i * names.len() + j]);
+            (name.to_string(), DVector::from_iterator(element.count, values))
+        })
+        .collect();
+    Ok(Ply {
+        colors: colors.map(|colors| {
+            Matrix::<U3>::from_fn(element.count, |i, j| data[i * names.len() + colors[j]])
+        }),
+        normals: normals.map(|normals| gather(&normals)),
+        positions: gather(&positions),
+        scalars: scalars,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {U2, utils};
+
+    #[test]
+    fn round_trip() {
+        let matrix = utils::random_matrix2(10);
+        let values = (0..10).map(|n| n as f64).collect::<Vec<_>>();
+        for &format in &[
+            Format::Ascii,
+            Format::BinaryLittleEndian,
+            Format::BinaryBigEndian,
+        ] {
+            let mut buffer = Vec::new();
+            Writer::new()
+                .format(format)
+                .scalar("value", &values)
+                .write(&mut buffer, &matrix)
+                .unwrap();
+            let ply = read::<U2, _>(&buffer[..]).unwrap();
+            assert_eq!(matrix, ply.positions);
+            assert_eq!(1, ply.scalars.len());
+            assert_eq!("value", ply.scalars[0].0);
+            assert_eq!(DVector::from_column_slice(&values), ply.scalars[0].1);
+        }
+    }
+
+    #[test]
+    fn normals_colors_and_faces() {
+        let text = "ply
+format ascii 1.0
+comment made by hand
+element vertex 3
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+property float intensity
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0 0.5
+1 0 0 0 0 1 0 255 0 0.6
+0 1 0 0 0 1 0 0 255 0.7
+3 0 1 2
+";
+        let ply = read::<U3, _>(text.as_bytes()).unwrap();
+        assert_eq!(3, ply.positions.nrows());
+        assert_eq!(1., ply.normals.unwrap()[(2, 2)]);
+        assert_eq!(255., ply.colors.unwrap()[(1, 1)]);
+        assert_eq!("intensity", ply.scalars[0].0);
+        assert!(read::<U3, _>("ply\nformat ascii 1.0\nend_header\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn big_endian_types() {
+        let mut buffer = b"ply\nformat binary_big_endian 1.0\nelement vertex 1\n\
+                           property short x\nproperty uint y\nend_header\n"
+            .to_vec();
+        buffer.extend_from_slice(&[0xff, 0xfe, 0, 0, 1, 0]);
+        let ply = read::<U2, _>(&buffer[..]).unwrap();
+        assert_eq!(-2., ply.positions[(0, 0)]);
+        assert_eq!(256., ply.positions[(0, 1)]);
+    }
+}
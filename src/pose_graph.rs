@@ -0,0 +1,777 @@
+//! Refine many pairwise registrations into globally consistent poses.
+//!
+//! When many point sets overlap, the pairwise transforms between them generally disagree around
+//! loops. A `PoseGraph` treats each point set as a node and each pairwise registration as an edge,
+//! and solves for the poses that best agree with all of the edges at once using Levenberg-Marquardt
+//! over SE(2) or SE(3).
+//!
+//! ```
+//! use cpd::{Rigid, U2, utils};
+//! use cpd::pose_graph::{Edge, PoseGraph};
+//! let scans = (0..3).map(|_| utils::random_matrix2(10)).collect::<Vec<_>>();
+//! let rigid = Rigid::new();
+//! let mut graph = PoseGraph::<U2>::new(scans.len());
+//! for &(fixed, moving) in &[(0, 1), (1, 2), (0, 2)] {
+//!     let run = rigid.register(&scans[fixed], &scans[moving]).unwrap();
+//!     graph.add(Edge::from_run(fixed, moving, &run));
+//! }
+//! let solution = graph.solve().unwrap();
+//! assert_eq!(3, solution.poses.len());
+//! ```
+
+use {PointTransform, Run, SquareMatrix, UInt, Vector};
+use failure::Error;
+use generic_array::ArrayLength;
+use nalgebra::{DMatrix, DVector, DimName, Matrix3, Rotation2, Rotation3, Vector3};
+use rigid::Transform;
+use std::f64;
+use std::ops::Mul;
+
+const STEP: f64 = 1e-6;
+const INITIAL_DAMPING: f64 = 1e-3;
+const MAX_DAMPING: f64 = 1e12;
+
+/// An error returned if an edge or the anchor refers to a node that is not in the graph.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Node {} is not in a graph of {} nodes", node, nodes)]
+pub struct InvalidNode {
+    /// The invalid node.
+    pub node: usize,
+    /// The number of nodes in the graph.
+    pub nodes: usize,
+}
+
+/// An error returned if a node is not connected to the anchor by any chain of edges.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Node {} is not connected to the anchor", _0)]
+pub struct Disconnected(pub usize);
+
+/// An error returned if the pose graph is asked to solve in a dimension other than two or three.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Pose graphs are only supported in two or three dimensions, not {}", _0)]
+pub struct UnsupportedDimension(pub usize);
+
+/// An error returned if an edge's information matrix is not square with one row per degree of
+/// freedom.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Edge {} has a {}x{} information matrix, expected {}x{}", edge, rows, columns,
+       dof, dof)]
+pub struct InformationShape {
+    /// The number of columns of the information matrix.
+    pub columns: usize,
+    /// The degrees of freedom of a pose, i.e. three in two dimensions and six in three.
+    pub dof: usize,
+    /// The index of the edge, in the order the edges were added.
+    pub edge: usize,
+    /// The number of rows of the information matrix.
+    pub rows: usize,
+}
+
+/// An error returned if the normal equations cannot be solved, e.g. because an edge has no
+/// weight.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "The pose graph's normal equations are singular")]
+pub struct Singular;
+
+/// A graph of poses connected by pairwise transforms.
+#[derive(Clone, Debug)]
+pub struct PoseGraph<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    anchor: usize,
+    edges: Vec<Edge<D>>,
+    max_iterations: usize,
+    nodes: usize,
+    tolerance: f64,
+}
+
+/// A pairwise transform between two nodes of a pose graph.
+#[derive(Clone, Debug)]
+pub struct Edge<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// The node that was used as the fixed point set.
+    pub fixed: usize,
+
+    /// The information matrix (inverse covariance) of this edge's residual.
+    ///
+    /// The residual is the translation error (`D` values, in the units of the input points)
+    /// followed by the rotation error (one angle in two dimensions, a rotation vector in three,
+    /// in radians), both in the moving node's frame.
+    pub information: DMatrix<f64>,
+
+    /// The node that was used as the moving point set.
+    pub moving: usize,
+
+    /// The transform that maps the moving node's points into the fixed node's frame.
+    ///
+    /// Any scale is ignored.
+    pub transform: Transform<D>,
+}
+
+/// The globally consistent poses of a pose graph.
+#[derive(Debug)]
+pub struct Solution<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Did the solver converge?
+    pub converged: bool,
+
+    /// The sum of squared edge residuals at the solution, weighted by each edge's information.
+    pub error: f64,
+
+    /// The number of Levenberg-Marquardt iterations, including rejected steps.
+    pub iterations: usize,
+
+    /// One pose per node, each mapping that node's points into the anchor's frame.
+    pub poses: Vec<Transform<D>>,
+}
+
+impl<D> PoseGraph<D>
+where
+    D: DimName,
+    UInt: Mul<<D as DimName>::Value>,
+    <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Creates a new pose graph with the given number of nodes and no edges.
+    ///
+    /// The anchor defaults to the first node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::pose_graph::PoseGraph;
+    /// let graph = PoseGraph::<U3>::new(4);
+    /// ```
+    pub fn new(nodes: usize) -> PoseGraph<D> {
+        PoseGraph {
+            anchor: 0,
+            edges: Vec::new(),
+            max_iterations: 50,
+            nodes: nodes,
+            tolerance: 1e-9,
+        }
+    }
+
+    /// Sets the anchor node, whose pose is held at the identity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::pose_graph::PoseGraph;
+    /// let graph = PoseGraph::<U3>::new(4).anchor(2);
+    /// ```
+    pub fn anchor(mut self, anchor: usize) -> PoseGraph<D> {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Sets the maximum number of Levenberg-Marquardt iterations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::pose_graph::PoseGraph;
+    /// let graph = PoseGraph::<U3>::new(4).max_iterations(10);
+    /// ```
+    pub fn max_iterations(mut self, max_iterations: usize) -> PoseGraph<D> {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Sets the convergence tolerance on the norm of a Levenberg-Marquardt step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::pose_graph::PoseGraph;
+    /// let graph = PoseGraph::<U3>::new(4).tolerance(1e-6);
+    /// ```
+    pub fn tolerance(mut self, tolerance: f64) -> PoseGraph<D> {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Adds an edge to this graph.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::pose_graph::{Edge, PoseGraph};
+    /// use cpd::rigid::Transform;
+    /// let mut graph = PoseGraph::<U2>::new(2);
+    /// graph.add(Edge::new(0, 1, Transform::identity(), 1., 1.));
+    /// ```
+    pub fn add(&mut self, edge: Edge<D>) {
+        self.edges.push(edge);
+    }
+
+    /// Solves for the poses that best agree with all edges.
+    ///
+    /// The initial poses are found by chaining edges outward from the anchor. Each iteration takes
+    /// a damped Gauss-Newton step, which is only accepted if it lowers the error. Rejected steps
+    /// increase the damping, so poor initial poses shorten the steps instead of diverging.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::pose_graph::{Edge, PoseGraph};
+    /// use cpd::rigid::Transform;
+    /// let mut graph = PoseGraph::<U2>::new(2);
+    /// graph.add(Edge::new(0, 1, Transform::identity(), 1., 1.));
+    /// let solution = graph.solve().unwrap();
+    /// assert!(solution.converged);
+    /// ```
+    pub fn solve(&self) -> Result<Solution<D>, Error> {
+        let dof = match D::dim() {
+            2 => 3,
+            3 => 6,
+            dim => return Err(UnsupportedDimension(dim).into()),
+        };
+        self.check(self.anchor)?;
+        for (i, edge) in self.edges.iter().enumerate() {
+            self.check(edge.fixed)?;
+            self.check(edge.moving)?;
+            if edge.information.shape() != (dof, dof) {
+                return Err(InformationShape {
+                    columns: edge.information.ncols(),
+                    dof: dof,
+                    edge: i,
+                    rows: edge.information.nrows(),
+                }.into());
+            }
+        }
+        let mut poses = self.initial_poses()?;
+        let index = |node: usize| match node {
+            node if node < self.anchor => Some(node * dof),
+            node if node > self.anchor => Some((node - 1) * dof),
+            _ => None,
+        };
+        let size = (self.nodes - 1) * dof;
+
+        let mut error = self.error(&poses);
+        let mut damping = INITIAL_DAMPING;
+        let mut iterations = 0;
+        let mut converged = false;
+        while iterations < self.max_iterations {
+            let mut hessian = DMatrix::<f64>::zeros(size, size);
+            let mut gradient = DVector::<f64>::zeros(size);
+            for edge in &self.edges {
+                let residual = edge.residual(&poses[edge.fixed], &poses[edge.moving]);
+                let weighted = &edge.information * &residual;
+                let mut columns = Vec::with_capacity(2 * dof);
+                for &node in &[edge.fixed, edge.moving] {
+                    let start = match index(node) {
+                        Some(start) => start,
+                        None => continue,
+                    };
+                    let perturb = |delta: &[f64]| {
+                        let perturbed = retract(&poses[node], delta);
+                        let pose = |other: usize| match other {
+                            other if other == node => &perturbed,
+                            other => &poses[other],
+                        };
+                        edge.residual(pose(edge.fixed), pose(edge.moving))
+                    };
+                    for k in 0..dof {
+                        let mut delta = vec![0.; dof];
+                        delta[k] = STEP;
+                        let plus = perturb(&delta);
+                        delta[k] = -STEP;
+                        let minus = perturb(&delta);
+                        columns.push((start + k, (plus - minus) / (2. * STEP)));
+                    }
+                }
+                for &(a, ref column_a) in &columns {
+                    gradient[a] += column_a.dot(&weighted);
+                    let information_a = &edge.information * column_a;
+                    for &(b, ref column_b) in &columns {
+                        hessian[(a, b)] += column_b.dot(&information_a);
+                    }
+                }
+            }
+            let step = loop {
+                let mut damped = hessian.clone();
+                for i in 0..size {
+                    damped[(i, i)] += damping * hessian[(i, i)];
+                }
+                let step = damped.cholesky().ok_or(Singular)?.solve(&(-&gradient));
+                let mut candidate = poses.clone();
+                for node in 0..self.nodes {
+                    if let Some(start) = index(node) {
+                        candidate[node] =
+                            retract(&poses[node], &step.as_slice()[start..start + dof]);
+                    }
+                }
+                let candidate_error = self.error(&candidate);
+                iterations += 1;
+                if candidate_error <= error {
+                    poses = candidate;
+                    error = candidate_error;
+                    damping = (damping / 10.).max(f64::EPSILON);
+                    break Some(step);
+                }
+                if step.norm() < self.tolerance {
+                    break Some(step);
+                }
+                damping *= 10.;
+                info!("iterations={}, rejected, damping={}", iterations, damping);
+                if iterations >= self.max_iterations || damping > MAX_DAMPING {
+                    break None;
+                }
+            };
+            let norm = match step {
+                Some(step) => step.norm(),
+                None => break,
+            };
+            info!("iterations={}, step={}, error={}", iterations, norm, error);
+            if norm < self.tolerance {
+                converged = true;
+                break;
+            }
+        }
+        Ok(Solution {
+            converged: converged,
+            error: error,
+            iterations: iterations,
+            poses: poses,
+        })
+    }
+
+    fn error(&self, poses: &[Transform<D>]) -> f64 {
+        self.edges
+            .iter()
+            .map(|edge| {
+                let residual = edge.residual(&poses[edge.fixed], &poses[edge.moving]);
+                residual.dot(&(&edge.information * &residual))
+            })
+            .sum()
+    }
+
+    fn check(&self, node: usize) -> Result<(), InvalidNode> {
+        if node < self.nodes {
+            Ok(())
+        } else {
+            Err(InvalidNode {
+                node: node,
+                nodes: self.nodes,
+            })
+        }
+    }
+
+    fn initial_poses(&self) -> Result<Vec<Transform<D>>, Disconnected> {
+        let mut poses = vec![None; self.nodes];
+        poses[self.anchor] = Some(Transform::identity());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for edge in &self.edges {
+                let pose = match (&poses[edge.fixed], &poses[edge.moving]) {
+                    (&Some(ref fixed), &None) => (edge.moving, fixed.compose(&edge.transform)),
+                    (&None, &Some(ref moving)) => {
+                        (edge.fixed, moving.compose(&invert(&edge.transform)))
+                    }
+                    _ => continue,
+                };
+                poses[pose.0] = Some(pose.1);
+                changed = true;
+            }
+        }
+        poses
+            .into_iter()
+            .enumerate()
+            .map(|(node, pose)| pose.ok_or(Disconnected(node)))
+            .collect()
+    }
+}
+
+impl<D> Edge<D>
+where
+    D: DimName,
+    UInt: Mul<<D as DimName>::Value>,
+    <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Creates a new edge from a transform that maps the moving node into the fixed node's frame.
+    ///
+    /// The translation weight is the inverse variance of the translation error, in the units of
+    /// the input points, and the rotation weight is the inverse variance of the rotation error, in
+    /// radians. Keeping them separate means that the relative weighting does not depend on the
+    /// scale of the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::pose_graph::Edge;
+    /// use cpd::rigid::Transform;
+    /// let edge = Edge::<U2>::new(0, 1, Transform::identity(), 1., 100.);
+    /// assert_eq!(100., edge.information[(2, 2)]);
+    /// ```
+    pub fn new(
+        fixed: usize,
+        moving: usize,
+        transform: Transform<D>,
+        translation_weight: f64,
+        rotation_weight: f64,
+    ) -> Edge<D> {
+        let dim = D::dim();
+        let dof = dim + dim * (dim - 1) / 2;
+        let information = DMatrix::from_fn(dof, dof, |i, j| match (i == j, i < dim) {
+            (true, true) => translation_weight,
+            (true, false) => rotation_weight,
+            (false, _) => 0.,
+        });
+        Edge::with_information(fixed, moving, transform, information)
+    }
+
+    /// Creates a new edge with a full information matrix.
+    ///
+    /// See `Edge::information` for the layout of the residual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate cpd;
+    /// extern crate nalgebra;
+    /// # fn main() {
+    /// use cpd::U2;
+    /// use cpd::pose_graph::Edge;
+    /// use cpd::rigid::Transform;
+    /// use nalgebra::DMatrix;
+    /// let information = DMatrix::identity(3, 3);
+    /// let edge = Edge::<U2>::with_information(0, 1, Transform::identity(), information);
+    /// # }
+    /// ```
+    pub fn with_information(
+        fixed: usize,
+        moving: usize,
+        transform: Transform<D>,
+        information: DMatrix<f64>,
+    ) -> Edge<D> {
+        Edge {
+            fixed: fixed,
+            information: information,
+            moving: moving,
+            transform: Transform {
+                scale: None,
+                ..transform
+            },
+        }
+    }
+
+    /// Creates a new edge from a rigid run.
+    ///
+    /// If the run has a covariance (see `Rigid::uncertainty`), the edge's information matrix is
+    /// its inverse, rotated into the edge's residual. Otherwise the edge is weighted by the
+    /// inverse of the run's final sigma2: the translation weight is `1 / sigma2`, and since a
+    /// rotation of `θ` radians moves points at a distance `r` from their centroid by about `rθ`,
+    /// the rotation weight is `r² / sigma2`, where `r²` is the mean squared distance of the moved
+    /// points from their centroid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// use cpd::pose_graph::Edge;
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let run = Rigid::new().register(&fixed, &moving).unwrap();
+    /// let edge = Edge::from_run(0, 1, &run);
+    /// ```
+    pub fn from_run(fixed: usize, moving: usize, run: &Run<D, Transform<D>>) -> Edge<D> {
+        let information = run.covariance
+            .as_ref()
+            .and_then(|covariance| information(&run.transform, covariance));
+        if let Some(information) = information {
+            return Edge::with_information(fixed, moving, run.transform.clone(), information);
+        }
+        let sigma2 = run.denormalized_sigma2.max(f64::EPSILON);
+        let radius2 = ::normalize::Parameters::new(&run.moved).scale.powi(2);
+        Edge::new(
+            fixed,
+            moving,
+            run.transform.clone(),
+            1. / sigma2,
+            radius2 / sigma2,
+        )
+    }
+
+    fn residual(&self, fixed: &Transform<D>, moving: &Transform<D>) -> DVector<f64> {
+        let error = invert(&self.transform).compose(&invert(fixed).compose(moving));
+        let rotation = log(&error.rotation);
+        DVector::from_iterator(
+            D::dim() + rotation.len(),
+            error.translation.iter().cloned().chain(rotation),
+        )
+    }
+}
+
+/// Inverts a pose or an edge's transform, ignoring any scale.
+///
+/// Without a scale the inverse always exists, so this does not go through
+/// `PointTransform::inverse`.
+fn invert<D>(transform: &Transform<D>) -> Transform<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    let rotation = transform.rotation.transpose();
+    Transform {
+        translation: -(&rotation * &transform.translation),
+        rotation: rotation,
+        scale: None,
+    }
+}
+
+/// Converts the covariance of a rigid transform's parameters into the information of an edge's
+/// residual, or `None` if the covariance cannot be inverted.
+///
+/// The covariance is over `[rotation, translation, scale]` (see `rigid::Uncertainty`), with the
+/// rotation applied after the transform's rotation, so the residual rotates both parts by the
+/// transform's inverse rotation. Any scale is marginalized out.
+fn information<D>(transform: &Transform<D>, covariance: &DMatrix<f64>) -> Option<DMatrix<f64>>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    let dim = D::dim();
+    let rotations = dim * (dim - 1) / 2;
+    let dof = dim + rotations;
+    if covariance.nrows() < dof || covariance.ncols() < dof {
+        return None;
+    }
+    let order = (rotations..dof).chain(0..rotations).collect::<Vec<_>>();
+    let covariance = DMatrix::from_fn(dof, dof, |i, j| covariance[(order[i], order[j])]);
+    let information = covariance.try_inverse()?;
+    let inverse = transform.rotation.transpose();
+    let jacobian = DMatrix::from_fn(dof, dof, |i, j| match (i < dim, j < dim) {
+        (true, true) => inverse[(i, j)],
+        (false, false) if rotations == 1 => 1.,
+        (false, false) => inverse[(i - dim, j - dim)],
+        _ => 0.,
+    });
+    let information = &jacobian * information * jacobian.transpose();
+    if information.iter().all(|n| n.is_finite()) {
+        Some(information)
+    } else {
+        None
+    }
+}
+
+/// Perturbs a pose on the right, i.e. `pose ∘ exp(delta)`.
+fn retract<D>(pose: &Transform<D>, delta: &[f64]) -> Transform<D>
+where
+    D: DimName,
+    UInt: Mul<<D as DimName>::Value>,
+    <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    let translation = Vector::<D>::from_fn(|i, _| delta[i]);
+    pose.compose(&Transform {
+        rotation: exp::<D>(&delta[D::dim()..]),
+        scale: None,
+        translation: translation,
+    })
+}
+
+fn exp<D>(tangent: &[f64]) -> SquareMatrix<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    if D::dim() == 2 {
+        let rotation = Rotation2::new(tangent[0]);
+        SquareMatrix::<D>::from_fn(|i, j| rotation.matrix()[(i, j)])
+    } else {
+        let rotation = Rotation3::new(Vector3::new(tangent[0], tangent[1], tangent[2]));
+        SquareMatrix::<D>::from_fn(|i, j| rotation.matrix()[(i, j)])
+    }
+}
+
+fn log<D>(rotation: &SquareMatrix<D>) -> Vec<f64>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    if D::dim() == 2 {
+        vec![rotation[(1, 0)].atan2(rotation[(0, 0)])]
+    } else {
+        let rotation = Rotation3::from_matrix_unchecked(Matrix3::from_fn(|i, j| rotation[(i, j)]));
+        rotation.scaled_axis().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {U2, U3};
+
+    fn transform2(angle: f64, x: f64, y: f64) -> Transform<U2> {
+        Transform {
+            rotation: *Rotation2::new(angle).matrix(),
+            scale: None,
+            translation: Vector::<U2>::new(x, y),
+        }
+    }
+
+    #[test]
+    fn consistent() {
+        let mut graph = PoseGraph::<U2>::new(3);
+        let a = transform2(0.1, 1., 0.);
+        let b = transform2(0.2, 0., 1.);
+        graph.add(Edge::new(0, 1, a.clone(), 1., 1.));
+        graph.add(Edge::new(1, 2, b.clone(), 1., 1.));
+        graph.add(Edge::new(0, 2, a.compose(&b), 1., 1.));
+        let solution = graph.solve().unwrap();
+        assert!(solution.converged);
+        assert!(solution.error < 1e-12);
+        assert_relative_eq!(a.compose(&b).rotation, solution.poses[2].rotation, epsilon = 1e-6);
+        assert_relative_eq!(
+            a.compose(&b).translation,
+            solution.poses[2].translation,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn loop_closure() {
+        let mut graph = PoseGraph::<U2>::new(4);
+        for node in 0..4 {
+            let transform = transform2(f64::consts::FRAC_PI_2 + 0.01, 1., 0.);
+            graph.add(Edge::new(node, (node + 1) % 4, transform, 1., 1.));
+        }
+        let solution = graph.solve().unwrap();
+        assert!(solution.converged);
+        assert!(solution.error < 0.04f64.powi(2));
+    }
+
+    #[test]
+    fn three_dimensions() {
+        let mut graph = PoseGraph::<U3>::new(2).anchor(1);
+        let mut transform = Transform::<U3>::identity();
+        transform.rotation = *Rotation3::new(Vector3::new(0.1, 0.2, 0.3)).matrix();
+        transform.translation = Vector::<U3>::new(1., 2., 3.);
+        graph.add(Edge::new(0, 1, transform.clone(), 1., 1.));
+        let solution = graph.solve().unwrap();
+        assert_relative_eq!(
+            transform.inverse().unwrap().rotation,
+            solution.poses[0].rotation,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn poor_initialization_does_not_diverge() {
+        let mut graph = PoseGraph::<U2>::new(4);
+        for node in 0..4 {
+            let transform = transform2(f64::consts::FRAC_PI_2 + 1.5, 10., -3.);
+            graph.add(Edge::new(node, (node + 1) % 4, transform, 1., 100.));
+        }
+        let initial = graph.clone().max_iterations(0).solve().unwrap();
+        let solution = graph.solve().unwrap();
+        assert!(solution.error.is_finite());
+        assert!(solution.error <= initial.error);
+    }
+
+    #[test]
+    fn rotation_weight() {
+        let mut graph = PoseGraph::<U2>::new(2);
+        graph.add(Edge::new(0, 1, transform2(0.2, 0., 0.), 1., 1e6));
+        graph.add(Edge::new(0, 1, transform2(0., 1., 0.), 1e6, 1.));
+        let solution = graph.solve().unwrap();
+        assert!(solution.converged);
+        assert_relative_eq!(0.2, log(&solution.poses[1].rotation)[0], epsilon = 1e-3);
+        assert_relative_eq!(
+            Vector::<U2>::new(1., 0.),
+            solution.poses[1].translation,
+            epsilon = 1e-3
+        );
+    }
+
+    #[test]
+    fn information_from_covariance() {
+        let transform = transform2(f64::consts::FRAC_PI_2, 1., 2.);
+        let covariance = DMatrix::from_diagonal(&DVector::from_column_slice(&[0.5, 2., 4.]));
+        let information = information(&transform, &covariance).unwrap();
+        assert_relative_eq!(0.25, information[(0, 0)], epsilon = 1e-12);
+        assert_relative_eq!(0.5, information[(1, 1)], epsilon = 1e-12);
+        assert_relative_eq!(2., information[(2, 2)], epsilon = 1e-12);
+        assert_relative_eq!(0., information[(0, 1)], epsilon = 1e-12);
+    }
+
+    #[test]
+    fn from_run_with_covariance() {
+        use {Rigid, utils};
+        use nalgebra::Rotation2;
+        use rigid::Uncertainty;
+
+        let fixed = utils::random_matrix2(20);
+        let moving = fixed.clone() * Rotation2::new(0.1) + utils::random_matrix2(20) * 0.01;
+        let run = Rigid::new()
+            .uncertainty(Uncertainty::ObservedInformation)
+            .register(&fixed, &moving)
+            .unwrap();
+        let edge = Edge::from_run(0, 1, &run);
+        assert_eq!((3, 3), edge.information.shape());
+        assert_relative_eq!(edge.information, edge.information.transpose(), epsilon = 1e-6);
+        let mut graph = PoseGraph::<U2>::new(2);
+        graph.add(edge);
+        assert!(graph.solve().unwrap().converged);
+    }
+
+    #[test]
+    fn information_shape() {
+        let mut graph = PoseGraph::<U2>::new(2);
+        let information = DMatrix::identity(2, 2);
+        graph.add(Edge::with_information(0, 1, Transform::identity(), information));
+        assert!(graph.solve().is_err());
+    }
+
+    #[test]
+    fn disconnected() {
+        let mut graph = PoseGraph::<U2>::new(3);
+        graph.add(Edge::new(0, 1, Transform::identity(), 1., 1.));
+        assert!(graph.solve().is_err());
+    }
+
+    #[test]
+    fn invalid_node() {
+        let mut graph = PoseGraph::<U2>::new(2);
+        graph.add(Edge::new(0, 2, Transform::identity(), 1., 1.));
+        assert!(graph.solve().is_err());
+    }
+}
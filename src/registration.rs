@@ -51,6 +51,37 @@ where
     /// ```
     fn transform(&self, moving: &Matrix<D>) -> Matrix<D>;
 
+    /// Sets this registration's transform parameters, e.g. to warm start from a known pose.
+    ///
+    /// The transform is in the same units as the transform returned by the registration, i.e.
+    /// normalize it with `normalize` before iterating on normalized points.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{U2, Rigid, Registration};
+    /// use cpd::rigid::Transform;
+    /// let rigid = Rigid::new();
+    /// let mut registration = rigid.as_registration::<U2>().unwrap();
+    /// registration.set_transform(&Transform::identity());
+    /// ```
+    fn set_transform(&mut self, transform: &Self::Transform);
+
+    /// Normalize the registration.
+    ///
+    /// This is the inverse of `denormalize`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{U2, Rigid, Registration, Normalization};
+    /// let rigid = Rigid::new();
+    /// let mut registration = rigid.as_registration::<U2>().unwrap();
+    /// let normalization = Normalization::default();
+    /// registration.normalize(&normalization);
+    /// ```
+    fn normalize(&mut self, normalization: &Normalization<D>);
+
     /// Denormalize the registration.
     ///
     /// # Examples
@@ -0,0 +1,288 @@
+//! Read and write rigid transforms in text formats understood by other tools.
+
+use {SquareMatrix, Vector};
+#[cfg(feature = "serde")]
+use UInt;
+use failure::Error;
+#[cfg(feature = "serde")]
+use generic_array::ArrayLength;
+#[cfg(feature = "serde")]
+use nalgebra::DimName;
+use nalgebra::{Matrix4, U3};
+use rigid::Transform;
+use std::io::{Read, Write};
+#[cfg(feature = "serde")]
+use std::ops::Mul;
+
+/// The tolerance used to decide whether a parsed matrix is a rotation, before it is projected onto
+/// the nearest orthonormal matrix.
+const ROTATION_TOLERANCE: f64 = 1e-4;
+
+/// The tolerance used to decide whether a parsed matrix has a scale.
+const SCALE_TOLERANCE: f64 = 1e-12;
+
+/// An error returned if a transform string does not contain exactly sixteen numbers.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "A 4x4 transform matrix needs 16 values, got {}", _0)]
+pub struct WrongNumberOfValues(pub usize);
+
+/// An error returned if a CloudCompare-style matrix is not four lines of four values each.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "A CloudCompare transform needs four lines of four values")]
+pub struct WrongShape;
+
+/// An error returned if a 4x4 matrix is not a rigid transform, e.g. if it shears.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "The 4x4 matrix is not a rigid transform")]
+pub struct NotRigid;
+
+impl Transform<U3> {
+    /// Returns this transform as a 4x4 row-major matrix on a single line, as taken by PDAL's
+    /// `filters.transformation`.
+    ///
+    /// The matrix maps moving coordinates into the fixed frame, `x_fixed = s·R·x_moving + t`, so
+    /// PDAL should apply it to the moving point cloud.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// assert_eq!(
+    ///     "1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1",
+    ///     Transform::<U3>::identity().to_pdal_string()
+    /// );
+    /// ```
+    pub fn to_pdal_string(&self) -> String {
+        let matrix = self.homogeneous3();
+        (0..4)
+            .flat_map(|i| (0..4).map(move |j| (i, j)))
+            .map(|(i, j)| matrix[(i, j)].to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parses a transform from a 4x4 row-major matrix, as taken by PDAL's
+    /// `filters.transformation`.
+    ///
+    /// The matrix is read as mapping moving coordinates into the fixed frame,
+    /// `x_fixed = s·R·x_moving + t`. Values can be separated by any whitespace. Returns an error if
+    /// the matrix is not a rigid transform. Rotations that are within 1e-4 of orthonormal, e.g.
+    /// because the values were rounded, are projected onto the nearest orthonormal matrix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let transform = Transform::from_pdal_str("1 0 0 1 0 1 0 2 0 0 1 3 0 0 0 1").unwrap();
+    /// assert_eq!(3., transform.translation[2]);
+    /// ```
+    pub fn from_pdal_str(s: &str) -> Result<Transform<U3>, Error> {
+        let values = s.split_whitespace()
+            .map(|value| value.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.len() != 16 {
+            return Err(WrongNumberOfValues(values.len()).into());
+        }
+        Transform::from_homogeneous3(&Matrix4::from_row_slice(&values))
+    }
+
+    /// Writes this transform as a CloudCompare-style matrix: four lines of four values each.
+    ///
+    /// Like CloudCompare's "Apply transformation" dialog, the matrix maps moving coordinates into
+    /// the fixed frame, `x_fixed = s·R·x_moving + t`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let mut buffer = Vec::new();
+    /// Transform::<U3>::identity().write_cloudcompare(&mut buffer).unwrap();
+    /// assert_eq!("1 0 0 0\n0 1 0 0\n0 0 1 0\n0 0 0 1\n", String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn write_cloudcompare<W: Write>(&self, mut write: W) -> Result<(), Error> {
+        let matrix = self.homogeneous3();
+        for i in 0..4 {
+            writeln!(
+                write,
+                "{} {} {} {}",
+                matrix[(i, 0)],
+                matrix[(i, 1)],
+                matrix[(i, 2)],
+                matrix[(i, 3)]
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads a transform from a CloudCompare-style matrix: four lines of four values each.
+    ///
+    /// The matrix is read as mapping moving coordinates into the fixed frame,
+    /// `x_fixed = s·R·x_moving + t`. Blank lines are ignored. Returns an error if there are not
+    /// exactly four lines of four values each, or if the matrix is not a rigid transform. Nearly
+    /// orthonormal rotations are projected as in `from_pdal_str`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let text = "1 0 0 1\n0 1 0 2\n0 0 1 3\n0 0 0 1\n";
+    /// let transform = Transform::read_cloudcompare(text.as_bytes()).unwrap();
+    /// assert_eq!(1., transform.translation[0]);
+    /// ```
+    pub fn read_cloudcompare<R: Read>(mut read: R) -> Result<Transform<U3>, Error> {
+        let mut s = String::new();
+        read.read_to_string(&mut s)?;
+        let lines = s.lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        if lines.len() != 4 || lines.iter().any(|line| line.split_whitespace().count() != 4) {
+            return Err(WrongShape.into());
+        }
+        Transform::from_pdal_str(&s)
+    }
+
+    fn from_homogeneous3(matrix: &Matrix4<f64>) -> Result<Transform<U3>, Error> {
+        let last_row = [matrix[(3, 0)], matrix[(3, 1)], matrix[(3, 2)], matrix[(3, 3)] - 1.];
+        if last_row.iter().any(|n| n.abs() > SCALE_TOLERANCE) {
+            return Err(NotRigid.into());
+        }
+        let linear = SquareMatrix::<U3>::from_fn(|i, j| matrix[(i, j)]);
+        let scale = linear.determinant().abs().cbrt();
+        if !scale.is_normal() {
+            return Err(NotRigid.into());
+        }
+        let rotation = linear / scale;
+        if (rotation.transpose() * rotation - SquareMatrix::<U3>::identity()).amax()
+            > ROTATION_TOLERANCE
+        {
+            return Err(NotRigid.into());
+        }
+        let svd = rotation.svd(true, true);
+        let rotation = match (svd.u, svd.v_t) {
+            (Some(u), Some(v_t)) => u * v_t,
+            _ => return Err(NotRigid.into()),
+        };
+        Ok(Transform {
+            rotation: rotation,
+            scale: if (scale - 1.).abs() > SCALE_TOLERANCE {
+                Some(scale)
+            } else {
+                None
+            },
+            translation: Vector::<U3>::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<D> Transform<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    SquareMatrix<D>: ::serde::Serialize + for<'de> ::serde::Deserialize<'de>,
+    Vector<D>: ::serde::Serialize + for<'de> ::serde::Deserialize<'de>,
+{
+    /// Returns this transform as JSON. Requires the `serde` feature.
+    ///
+    /// The JSON stores the rotation, scale, and translation of `x_fixed = s·R·x_moving + t`.
+    /// Matrices are stored column-major, as nalgebra serializes them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let json = Transform::<U2>::identity().to_json().unwrap();
+    /// assert_eq!(Transform::identity(), Transform::<U2>::from_json(&json).unwrap());
+    /// ```
+    pub fn to_json(&self) -> Result<String, Error> {
+        ::serde_json::to_string_pretty(self).map_err(Error::from)
+    }
+
+    /// Parses a transform from JSON, as written by `to_json`. Requires the `serde` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let json = Transform::<U2>::identity().to_json().unwrap();
+    /// let transform = Transform::<U2>::from_json(&json).unwrap();
+    /// ```
+    pub fn from_json(json: &str) -> Result<Transform<D>, Error> {
+        ::serde_json::from_str(json).map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform() -> Transform<U3> {
+        Transform {
+            scale: Some(2.),
+            translation: Vector::<U3>::new(1., 2., 3.),
+            ..Transform::from_yaw_pitch_roll(0.3, 0.2, 0.1)
+        }
+    }
+
+    #[test]
+    fn pdal() {
+        let transform = transform();
+        let other = Transform::from_pdal_str(&transform.to_pdal_string()).unwrap();
+        assert_relative_eq!(transform.rotation, other.rotation, epsilon = 1e-12);
+        assert_relative_eq!(transform.translation, other.translation, epsilon = 1e-12);
+        assert_relative_eq!(2., other.scale.unwrap(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn cloudcompare() {
+        let transform = transform();
+        let mut buffer = Vec::new();
+        transform.write_cloudcompare(&mut buffer).unwrap();
+        assert_eq!(4, String::from_utf8(buffer.clone()).unwrap().lines().count());
+        let other = Transform::read_cloudcompare(&buffer[..]).unwrap();
+        assert_relative_eq!(transform.rotation, other.rotation, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn orthonormalized() {
+        let transform = Transform::from_pdal_str(
+            "0.99999 0.00001 0 0 0 1 0 0 0 0 1.00002 0 0 0 0 1",
+        ).unwrap();
+        assert_relative_eq!(
+            SquareMatrix::<U3>::identity(),
+            transform.rotation.transpose() * transform.rotation,
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(1., transform.rotation.determinant(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Transform::from_pdal_str("1 0 0 0").is_err());
+        assert!(Transform::from_pdal_str("1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 x").is_err());
+        assert!(Transform::from_pdal_str("1 1 0 0 0 1 0 0 0 0 1 0 0 0 0 1").is_err());
+        assert!(Transform::from_pdal_str("1 0 0 0 0 1 0 0 0 0 1 0 1 0 0 1").is_err());
+        let one_line = "1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1\n";
+        assert!(Transform::read_cloudcompare(one_line.as_bytes()).is_err());
+        let ragged = "1 0 0 0 0\n1 0 0\n0 0 1 0\n0 0 0 1\n";
+        assert!(Transform::read_cloudcompare(ragged.as_bytes()).is_err());
+        let blank = "\n1 0 0 0\n0 1 0 0\n\n0 0 1 0\n0 0 0 1\n\n";
+        assert!(Transform::read_cloudcompare(blank.as_bytes()).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let transform = transform();
+        let json = transform.to_json().unwrap();
+        assert_eq!(transform, Transform::from_json(&json).unwrap());
+    }
+}
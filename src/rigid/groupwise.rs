@@ -0,0 +1,311 @@
+use {Matrix, Normalize, PointTransform, Registration as RegistrationTrait, Rigid, UInt, validate};
+use failure::Error;
+use gauss_transform::Transformer;
+use generic_array::ArrayLength;
+use nalgebra::{DVector, DefaultAllocator, DimMin, DimName, DimSub, U1};
+use nalgebra::allocator::Allocator;
+use normalize::Parameters;
+use rigid::{Registration, Transform};
+use runner::{self, Termination};
+use std::f64;
+use std::ops::Mul;
+use std::time::Instant;
+
+/// An error returned if a groupwise registration is given fewer than two point sets.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Groupwise registration requires at least two point sets, got {}", _0)]
+pub struct TooFewPointSets(pub usize);
+
+/// An error returned if the transform of a point set cannot be inverted.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "The transform of point set {} is not invertible", _0)]
+pub struct NotInvertible(pub usize);
+
+/// Build and run groupwise rigid registrations.
+///
+/// Groupwise registration jointly registers any number of point sets to a common set of Gaussian
+/// mixture centers, which is estimated along with the transforms (JRMPC-style). This avoids the
+/// error accumulation of chaining pairwise registrations.
+///
+/// Groupwise registrations never scale. The runner's normalization strategy is ignored, since all
+/// point sets are normalized together.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{Rigid, utils};
+/// let views = (0..3).map(|_| utils::random_matrix2(10)).collect::<Vec<_>>();
+/// let run = Rigid::new().groupwise().centers(8).register(&views).unwrap();
+/// assert_eq!(3, run.transforms.len());
+/// assert_eq!(8, run.centers.nrows());
+/// ```
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Groupwise {
+    centers: Option<usize>,
+    rigid: Rigid,
+}
+
+/// The result of a groupwise rigid registration.
+#[derive(Debug)]
+pub struct GroupwiseRun<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// The estimated Gaussian mixture centers, in the common frame.
+    pub centers: Matrix<D>,
+
+    /// Did this run converge?
+    pub converged: bool,
+
+    /// The number of iterations.
+    pub iterations: usize,
+
+    /// The final sigma2, in the units of the input points.
+    pub sigma2: f64,
+
+    /// Why the run stopped iterating.
+    pub termination: Termination,
+
+    /// One transform per input point set, each mapping that point set into the common frame.
+    pub transforms: Vec<Transform<D>>,
+}
+
+impl Groupwise {
+    /// Creates a new groupwise registration builder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::rigid::Groupwise;
+    /// let groupwise = Groupwise::new();
+    /// ```
+    pub fn new() -> Groupwise {
+        Groupwise::default()
+    }
+
+    /// Sets the number of Gaussian mixture centers.
+    ///
+    /// The centers are initialized from the first point set. If none, use every point of the first
+    /// point set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::rigid::Groupwise;
+    /// let groupwise = Groupwise::new().centers(100);
+    /// ```
+    pub fn centers<T: Into<Option<usize>>>(mut self, centers: T) -> Groupwise {
+        self.centers = centers.into();
+        self
+    }
+
+    /// Jointly registers the point sets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::utils;
+    /// use cpd::rigid::Groupwise;
+    /// let views = vec![utils::random_matrix2(10), utils::random_matrix2(10)];
+    /// let run = Groupwise::new().register(&views).unwrap();
+    /// ```
+    pub fn register<D>(&self, views: &[Matrix<D>]) -> Result<GroupwiseRun<D>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let start = Instant::now();
+        if views.len() < 2 {
+            return Err(TooFewPointSets(views.len()).into());
+        }
+        for view in views {
+            validate::point_set(view)?;
+        }
+        let parameters = Parameters::new(&concatenate(views));
+        let views = views
+            .iter()
+            .map(|view| {
+                let mut view = view.clone();
+                parameters.normalize(&mut view);
+                view
+            })
+            .collect::<Vec<_>>();
+        let stride = self.centers
+            .map_or(1, |centers| (views[0].nrows() / centers.max(1)).max(1));
+        let mut centers = ::utils::decimate(&views[0], stride).into_owned();
+        if let Some(ncenters) = self.centers {
+            centers = Matrix::<D>::from_fn(ncenters.min(centers.nrows()), |i, j| centers[(i, j)]);
+        }
+
+        let rigid = Rigid {
+            runner: self.rigid.runner.clone().normalize(Normalize::None),
+            scale: false,
+            ..self.rigid.clone()
+        };
+        let mut registrations = views
+            .iter()
+            .map(|_| Registration::new(&rigid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let transformers = views
+            .iter()
+            .map(|view| Transformer::new(view, rigid.runner.outlier_weight))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut sigma2 = rigid.runner.sigma2.unwrap_or_else(|| {
+            views
+                .iter()
+                .map(|view| runner::sigma2(view, &centers))
+                .sum::<f64>() / views.len() as f64
+        });
+
+        let mut error = 0.;
+        let mut error_change = f64::MAX;
+        let mut iterations = 0;
+        let termination = loop {
+            if let Some(termination) = rigid
+                .runner
+                .termination(start, iterations, error_change, sigma2)
+            {
+                break termination;
+            }
+            let mut numerator = Matrix::<D>::zeros(centers.nrows());
+            let mut denominator = DVector::<f64>::zeros(centers.nrows());
+            let mut sum_of_squares = 0.;
+            let mut next_error = 0.;
+            for (i, ((view, transformer), registration)) in views
+                .iter()
+                .zip(transformers.iter())
+                .zip(registrations.iter_mut())
+                .enumerate()
+            {
+                let probabilities =
+                    transformer.probabilities(&registration.transform(&centers), sigma2);
+                next_error += probabilities.error;
+                registration.iterate(view, &centers, &probabilities);
+                let transform = Transform::from(registration.clone())
+                    .inverse()
+                    .ok_or(NotInvertible(i))?;
+                numerator += &probabilities.px * transform.rotation.transpose();
+                for d in 0..D::dim() {
+                    let mut column = numerator.column_mut(d);
+                    column += transform.translation[d] * &probabilities.p1;
+                }
+                denominator += &probabilities.p1;
+                let moved = transform.apply(view);
+                sum_of_squares += (0..moved.nrows())
+                    .map(|i| probabilities.pt1[i] * moved.row(i).norm_squared())
+                    .sum::<f64>();
+            }
+            for k in 0..centers.nrows() {
+                if denominator[k] > f64::EPSILON {
+                    for d in 0..D::dim() {
+                        centers[(k, d)] = numerator[(k, d)] / denominator[k];
+                    }
+                }
+            }
+            let center_sum_of_squares = (0..centers.nrows())
+                .map(|k| denominator[k] * centers.row(k).norm_squared())
+                .sum::<f64>();
+            sigma2 = ((sum_of_squares - center_sum_of_squares)
+                / (D::dim() as f64 * denominator.iter().sum::<f64>()))
+                .abs();
+            error_change = ((next_error - error) / next_error).abs();
+            info!(
+                "iterations={}, error_change={}, sigma2={}",
+                iterations, error_change, sigma2
+            );
+            error = next_error;
+            iterations += 1;
+        };
+
+        parameters.denormalize(&mut centers);
+        let transforms = registrations
+            .into_iter()
+            .enumerate()
+            .map(|(i, registration)| {
+                let mut transform = Transform::from(registration)
+                    .inverse()
+                    .ok_or(NotInvertible(i))?;
+                transform.translation = parameters.scale * &transform.translation
+                    + &parameters.offset
+                    - &transform.rotation * &parameters.offset;
+                Ok(transform)
+            })
+            .collect::<Result<Vec<_>, NotInvertible>>()?;
+        Ok(GroupwiseRun {
+            centers: centers,
+            converged: termination.is_converged(),
+            iterations: iterations,
+            sigma2: sigma2 * parameters.scale.powi(2),
+            termination: termination,
+            transforms: transforms,
+        })
+    }
+}
+
+impl From<Rigid> for Groupwise {
+    fn from(rigid: Rigid) -> Groupwise {
+        Groupwise {
+            rigid: rigid,
+            ..Default::default()
+        }
+    }
+}
+
+fn concatenate<D>(matrices: &[Matrix<D>]) -> Matrix<D>
+where
+    D: DimName,
+{
+    let nrows = matrices.iter().map(|matrix| matrix.nrows()).sum();
+    let mut concatenated = Matrix::<D>::zeros(nrows);
+    let mut offset = 0;
+    for matrix in matrices {
+        for i in 0..matrix.nrows() {
+            for d in 0..D::dim() {
+                concatenated[(offset + i, d)] = matrix[(i, d)];
+            }
+        }
+        offset += matrix.nrows();
+    }
+    concatenated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils;
+    use nalgebra::Rotation2;
+
+    #[test]
+    fn too_few_point_sets() {
+        let views = vec![utils::random_matrix2(10)];
+        assert!(Groupwise::new().register(&views).is_err());
+    }
+
+    #[test]
+    fn aligns_views() {
+        let fixed = utils::random_matrix2(30);
+        let mut views = vec![fixed.clone()];
+        for &(angle, dx) in &[(0.1, 0.2), (-0.15, -0.1)] {
+            let mut view = fixed.clone() * Rotation2::new(angle);
+            view.column_mut(0).add_scalar_mut(dx);
+            views.push(view);
+        }
+        let run = Groupwise::new().register(&views).unwrap();
+        assert_eq!(3, run.transforms.len());
+        let common = run.transforms[0].apply(&views[0]);
+        for (view, transform) in views.iter().zip(run.transforms.iter()).skip(1) {
+            assert_relative_eq!(common, transform.apply(view), epsilon = 1e-3);
+        }
+    }
+}
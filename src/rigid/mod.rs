@@ -23,17 +23,53 @@
 //! let matrix = utils::random_matrix2(10);
 //! let run = Rigid::new().register(&matrix, &matrix).unwrap();
 //! ```
+//!
+//! To jointly register more than two point sets to a common, estimated set of centers, use a
+//! groupwise registration:
+//!
+//! ```
+//! use cpd::{Rigid, utils};
+//! let views = vec![utils::random_matrix2(10), utils::random_matrix2(10)];
+//! let run = Rigid::new().groupwise().register(&views).unwrap();
+//! ```
+//!
+//! Rigid registrations can converge to local minima for large rotations or symmetric objects. Use
+//! `register_multi_start` to run from several starting rotations and rank the results:
+//!
+//! ```
+//! use cpd::{Rigid, utils};
+//! use cpd::rigid;
+//! let matrix = utils::random_matrix2(10);
+//! let multi_start = Rigid::new()
+//!     .register_multi_start(&matrix, &matrix, &rigid::rotations2(8))
+//!     .unwrap();
+//! ```
 
+mod formats;
+mod groupwise;
+mod multi_start;
+mod odometry;
 mod registration;
+mod symmetric;
 mod transform;
+mod uncertainty;
 
+pub use self::formats::{NotRigid, WrongNumberOfValues, WrongShape};
+pub use self::groupwise::{Groupwise, GroupwiseRun, NotInvertible, TooFewPointSets};
+pub use self::multi_start::{MultiStart, rotations2, rotations3};
+pub use self::odometry::{Odometry, Step};
 pub use self::registration::{CannotNormalizeIndependentlyWithoutScale, Registration};
+pub use self::symmetric::{Consistency, Symmetric};
 pub use self::transform::Transform;
+pub use self::uncertainty::Uncertainty;
 
-use {Matrix, Run, Runner, UInt};
+use {Matrix, PointTransform, Run, Runner, SquareMatrix, UInt};
 use failure::Error;
+#[cfg(feature = "rayon")]
+use normalize::Parameters;
+use runner::Batch;
 use generic_array::ArrayLength;
-use nalgebra::{DefaultAllocator, DimMin, DimName, DimSub, U1};
+use nalgebra::{DMatrix, DefaultAllocator, DimMin, DimName, DimSub, U1};
 use nalgebra::allocator::Allocator;
 use std::ops::Mul;
 
@@ -55,11 +91,13 @@ use std::ops::Mul;
 /// let matrix = utils::random_matrix2(10);
 /// let run = Rigid::new().register(&matrix, &matrix).unwrap();
 /// ```
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Rigid {
     allow_reflections: bool,
     runner: Runner,
     scale: bool,
+    uncertainty: Option<Uncertainty>,
 }
 
 impl Rigid {
@@ -101,6 +139,39 @@ impl Rigid {
         self
     }
 
+    /// Sets how to estimate the covariance of the transform parameters, if at all.
+    ///
+    /// When set, every registration fills `Run::covariance` for its returned transform. The
+    /// default is not to estimate a covariance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// use cpd::rigid::Uncertainty;
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let rigid = Rigid::new().uncertainty(Uncertainty::ObservedInformation);
+    /// let run = rigid.register(&fixed, &moving).unwrap();
+    /// assert_eq!((3, 3), run.covariance.unwrap().shape());
+    /// ```
+    pub fn uncertainty<T: Into<Option<Uncertainty>>>(mut self, uncertainty: T) -> Rigid {
+        self.uncertainty = uncertainty.into();
+        self
+    }
+
+    /// Returns a groupwise registration builder that will use this rigid configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Rigid;
+    /// let groupwise = Rigid::new().groupwise();
+    /// ```
+    pub fn groupwise(self) -> Groupwise {
+        self.into()
+    }
+
     /// Returns this rigid configuration as a registration.
     ///
     /// # Examples
@@ -150,8 +221,380 @@ impl Rigid {
             + Allocator<f64, <D as DimSub<U1>>::Output>,
     {
         let registration = self.as_registration()?;
-        let tuple = self.runner.run(fixed, moving, registration)?;
-        Ok(tuple)
+        let run = self.runner.run(fixed, moving, registration)?;
+        self.with_covariance(fixed, moving, run)
+    }
+
+    /// Registers two matrices, starting from an initial transform.
+    ///
+    /// The initial transform maps the moving points onto the fixed points, in the units of the
+    /// input points. If scaling is disabled, the initial transform's scale is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// use cpd::rigid::Transform;
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let rigid = Rigid::new();
+    /// let run = rigid.register_from(&fixed, &moving, &Transform::identity()).unwrap();
+    /// ```
+    pub fn register_from<D>(
+        &self,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        initial: &Transform<D>,
+    ) -> Result<Run<D, Transform<D>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let registration = self.as_registration()?;
+        let run = self.runner.run_from(fixed, moving, registration, initial)?;
+        self.with_covariance(fixed, moving, run)
+    }
+
+    /// Estimates the covariance of a run's transform parameters after the fact.
+    ///
+    /// Prefer setting `uncertainty` on the builder, which fills `Run::covariance` as part of the
+    /// registration. This is useful to try another strategy on an existing run. The run should be
+    /// the result of registering these moving points onto these fixed points with this rigid
+    /// configuration. See `Uncertainty` for the layout of the parameters. Returns `None` if no
+    /// covariance can be estimated, e.g. because the run's sigma2 has collapsed to zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// use cpd::rigid::Uncertainty;
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let rigid = Rigid::new();
+    /// let run = rigid.register(&fixed, &moving).unwrap();
+    /// let covariance = rigid
+    ///     .covariance(&fixed, &moving, &run, Uncertainty::ObservedInformation)
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!((3, 3), covariance.shape());
+    /// ```
+    pub fn covariance<D>(
+        &self,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        run: &Run<D, Transform<D>>,
+        uncertainty: Uncertainty,
+    ) -> Result<Option<DMatrix<f64>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        uncertainty.covariance(self, fixed, moving, run)
+    }
+
+    fn with_covariance<D>(
+        &self,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        mut run: Run<D, Transform<D>>,
+    ) -> Result<Run<D, Transform<D>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        if let Some(uncertainty) = self.uncertainty {
+            run.covariance = uncertainty.covariance(self, fixed, moving, &run)?;
+        }
+        Ok(run)
+    }
+
+    /// Registers the moving points onto the fixed points, and the fixed points onto the moving
+    /// points.
+    ///
+    /// Compares the forward transform with the inverse of the backward transform, which is a cheap
+    /// way to detect registrations that landed in a wrong local minimum. Use
+    /// `Symmetric::average` to combine the two into a symmetric estimate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = fixed.clone();
+    /// let symmetric = Rigid::new().register_symmetric(&fixed, &moving).unwrap();
+    /// assert!(symmetric.consistency.rotation < 1e-6);
+    /// ```
+    pub fn register_symmetric<D>(
+        &self,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+    ) -> Result<Symmetric<D>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let forward = self.register(fixed, moving)?;
+        let backward = self.register(moving, fixed)?;
+        Ok(Symmetric::new(moving, forward, backward))
+    }
+
+    /// Registers many moving matrices against one fixed matrix.
+    ///
+    /// The fixed matrix is validated and its normalization parameters are calculated only once.
+    /// Returns an error if the fixed matrix is invalid, otherwise returns one result per moving
+    /// matrix, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// let fixed = utils::random_matrix2(10);
+    /// let movings = vec![utils::random_matrix2(10), utils::random_matrix2(12)];
+    /// let runs = Rigid::new().register_batch(&fixed, &movings).unwrap();
+    /// assert_eq!(2, runs.len());
+    /// ```
+    pub fn register_batch<D>(
+        &self,
+        fixed: &Matrix<D>,
+        movings: &[Matrix<D>],
+    ) -> Result<Vec<Result<Run<D, Transform<D>>, Error>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let batch = self.runner.batch(fixed)?;
+        Ok(movings
+            .iter()
+            .map(|moving| self.register_in_batch(fixed, &batch, moving))
+            .collect())
+    }
+
+    /// Registers many moving matrices against one fixed matrix, in parallel.
+    ///
+    /// Behaves like `register_batch`, but uses [rayon](https://crates.io/crates/rayon) to run the
+    /// registrations in parallel. Requires the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// let fixed = utils::random_matrix2(10);
+    /// let movings = vec![utils::random_matrix2(10), utils::random_matrix2(12)];
+    /// let runs = Rigid::new().par_register_batch(&fixed, &movings).unwrap();
+    /// assert_eq!(2, runs.len());
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_register_batch<D>(
+        &self,
+        fixed: &Matrix<D>,
+        movings: &[Matrix<D>],
+    ) -> Result<Vec<Result<Run<D, Transform<D>>, Error>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+        Matrix<D>: Sync,
+        Parameters<D>: Sync,
+        Run<D, Transform<D>>: Send,
+    {
+        use rayon::prelude::*;
+        let batch = self.runner.batch(fixed)?;
+        Ok(movings
+            .par_iter()
+            .map(|moving| self.register_in_batch(fixed, &batch, moving))
+            .collect())
+    }
+
+    fn register_in_batch<D>(
+        &self,
+        fixed: &Matrix<D>,
+        batch: &Batch<D>,
+        moving: &Matrix<D>,
+    ) -> Result<Run<D, Transform<D>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let registration = self.as_registration()?;
+        let run = batch.run(moving, registration)?;
+        self.with_covariance(fixed, moving, run)
+    }
+
+    /// Registers two matrices once for each starting rotation, ranking the runs by final error.
+    ///
+    /// Each run starts by rotating the moving points about their centroid and moving that
+    /// centroid onto the fixed points' centroid. Use `rotations2` or `rotations3` to generate
+    /// evenly-spaced starting rotations, or provide your own. Inspect the ranked runs to detect
+    /// ambiguous registrations, e.g. several distinct rotations with similar errors.
+    ///
+    /// Starts that fail, e.g. because they diverge, are skipped and recorded in
+    /// `MultiStart::failures`. This returns an error only if every start fails, in which case it
+    /// is the error from the first start.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// use cpd::rigid;
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let multi_start = Rigid::new()
+    ///     .register_multi_start(&fixed, &moving, &rigid::rotations2(4))
+    ///     .unwrap();
+    /// assert_eq!(4, multi_start.runs.len());
+    /// ```
+    pub fn register_multi_start<D>(
+        &self,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        rotations: &[SquareMatrix<D>],
+    ) -> Result<MultiStart<D>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let mut runs = Vec::with_capacity(rotations.len());
+        let mut failures = Vec::new();
+        for (i, rotation) in rotations.iter().enumerate() {
+            let initial = multi_start::centroid_transform(fixed, moving, rotation);
+            match self.register_from(fixed, moving, &initial) {
+                Ok(run) => runs.push(run),
+                Err(err) => {
+                    warn!("start={}, error={}", i, err);
+                    failures.push((i, err));
+                }
+            }
+        }
+        if runs.is_empty() && !failures.is_empty() {
+            return Err(failures.swap_remove(0).1);
+        }
+        MultiStart::new(fixed, self.runner.outlier_weight, runs, failures)
+    }
+
+    /// Registers a sequence of point sets, each to the one before it, and accumulates the
+    /// resulting motions into global poses.
+    ///
+    /// Each registration is seeded with the previous step's motion, which works well when the
+    /// motion between point sets is smooth, e.g. for mobile scanning. Only two point sets are held
+    /// in memory at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// let frames = (0..3).map(|_| utils::random_matrix2(10));
+    /// let odometry = Rigid::new().register_sequence(frames).unwrap();
+    /// assert_eq!(3, odometry.poses.len());
+    /// assert_eq!(2, odometry.steps.len());
+    /// ```
+    pub fn register_sequence<D, I>(&self, frames: I) -> Result<Odometry<D>, Error>
+    where
+        I: IntoIterator<Item = Matrix<D>>,
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        let mut frames = frames.into_iter();
+        let mut odometry = Odometry {
+            poses: Vec::new(),
+            steps: Vec::new(),
+        };
+        let mut previous = match frames.next() {
+            Some(frame) => frame,
+            None => return Ok(odometry),
+        };
+        odometry.poses.push(Transform::identity());
+        let mut accumulated_residual = 0.;
+        for frame in frames {
+            let run = match odometry.steps.last() {
+                Some(step) => self.register_from(&previous, &frame, &step.motion)?,
+                None => self.register(&previous, &frame)?,
+            };
+            accumulated_residual += run.denormalized_sigma2.sqrt();
+            let pose = odometry.poses
+                .last()
+                .expect("poses always has the first pose")
+                .compose(&run.transform);
+            info!(
+                "step={}, iterations={}, accumulated_residual={}",
+                odometry.steps.len(),
+                run.iterations,
+                accumulated_residual
+            );
+            odometry.poses.push(pose);
+            odometry.steps.push(Step {
+                accumulated_residual: accumulated_residual,
+                converged: run.converged,
+                denormalized_sigma2: run.denormalized_sigma2,
+                iterations: run.iterations,
+                motion: run.transform,
+                runtime: run.runtime,
+                termination: run.termination,
+            });
+            previous = frame;
+        }
+        Ok(odometry)
     }
 }
 
@@ -252,6 +695,28 @@ mod tests {
         }
     }
 
+    mod batch {
+        use {Matrix, Rigid, U2, utils};
+
+        #[test]
+        fn errors_per_item() {
+            let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+            let movings = vec![fixed.clone(), Matrix::<U2>::zeros(0), fixed.clone()];
+            let runs = Rigid::new().register_batch(&fixed, &movings).unwrap();
+            assert_eq!(3, runs.len());
+            assert!(runs[0].is_ok());
+            assert!(runs[1].is_err());
+            assert_relative_eq!(fixed, runs[2].as_ref().unwrap().moved, epsilon = 1e-4);
+        }
+
+        #[test]
+        fn invalid_fixed() {
+            let fixed = Matrix::<U2>::zeros(0);
+            let movings = vec![utils::random_matrix2(10)];
+            assert!(Rigid::new().register_batch(&fixed, &movings).is_err());
+        }
+    }
+
     rigid!(independent_and_scale, Normalize::Independent, true);
     rigid!(same_scale_and_scale, Normalize::SameScale, true);
     rigid!(same_scale_no_scale, Normalize::SameScale, false);
@@ -0,0 +1,259 @@
+use {Matrix, Run, SquareMatrix, UInt};
+use failure::Error;
+use gauss_transform::Transformer;
+use generic_array::ArrayLength;
+use nalgebra::{DimName, Quaternion, Rotation2, U2, U3, UnitQuaternion};
+use rigid::Transform;
+use std::cmp::Ordering;
+use std::f64;
+use std::f64::consts::PI;
+use std::ops::Mul;
+
+/// The result of a multi-start rigid registration.
+#[derive(Debug)]
+pub struct MultiStart<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// The error (negative log-likelihood) of each run's final state, in the units of the input
+    /// points, in the same order as `runs`.
+    ///
+    /// The error is NaN for runs that did not complete any iterations, e.g. because they were
+    /// cancelled or timed out.
+    pub errors: Vec<f64>,
+
+    /// The starts that failed, as the index of the starting rotation and the error it failed
+    /// with.
+    ///
+    /// Failed starts are skipped, so they have no entry in `runs` or `errors`.
+    pub failures: Vec<(usize, Error)>,
+
+    /// The runs from every starting rotation, ranked by final error, best first.
+    ///
+    /// Runs with a non-finite error are ranked last.
+    pub runs: Vec<Run<D, Transform<D>>>,
+}
+
+impl<D> MultiStart<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    pub(crate) fn new(
+        fixed: &Matrix<D>,
+        outlier_weight: f64,
+        runs: Vec<Run<D, Transform<D>>>,
+        failures: Vec<(usize, Error)>,
+    ) -> Result<MultiStart<D>, Error>
+    where
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+    {
+        let transformer = Transformer::new(fixed, outlier_weight)?;
+        let mut ranked = runs.into_iter()
+            .map(|run| {
+                let error = if run.iterations == 0 {
+                    f64::NAN
+                } else {
+                    transformer
+                        .probabilities(&run.moved, run.denormalized_sigma2)
+                        .error
+                };
+                (error, run)
+            })
+            .collect::<Vec<_>>();
+        ranked.sort_by(|&(a, _), &(b, _)| match (a.is_finite(), b.is_finite()) {
+            (true, true) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => Ordering::Equal,
+        });
+        let (errors, runs) = ranked.into_iter().unzip();
+        Ok(MultiStart {
+            errors: errors,
+            failures: failures,
+            runs: runs,
+        })
+    }
+
+    /// Returns the run with the lowest final error, if there were any starting rotations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// use cpd::rigid;
+    /// let matrix = utils::random_matrix2(10);
+    /// let multi_start = Rigid::new()
+    ///     .register_multi_start(&matrix, &matrix, &rigid::rotations2(4))
+    ///     .unwrap();
+    /// let best = multi_start.best().unwrap();
+    /// ```
+    pub fn best(&self) -> Option<&Run<D, Transform<D>>> {
+        self.runs.first()
+    }
+}
+
+/// Returns a transform with this rotation and the translation that maps the moving points'
+/// centroid onto the fixed points' centroid.
+pub(crate) fn centroid_transform<D>(
+    fixed: &Matrix<D>,
+    moving: &Matrix<D>,
+    rotation: &SquareMatrix<D>,
+) -> Transform<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    use normalize::Parameters;
+    let fixed = Parameters::new(fixed).offset;
+    let moving = Parameters::new(moving).offset;
+    Transform {
+        rotation: rotation.clone(),
+        scale: None,
+        translation: fixed - rotation * moving,
+    }
+}
+
+/// Returns `n` rotations evenly spaced around the circle, starting with the identity.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::rigid;
+/// let rotations = rigid::rotations2(4);
+/// assert_eq!(4, rotations.len());
+/// ```
+pub fn rotations2(n: usize) -> Vec<SquareMatrix<U2>> {
+    (0..n)
+        .map(|i| *Rotation2::new(2. * PI * i as f64 / n as f64).matrix())
+        .collect()
+}
+
+/// Returns `n` rotations approximately uniformly distributed over SO(3).
+///
+/// The rotations are generated with a super-Fibonacci spiral, which is deterministic and covers
+/// the rotation group evenly for any `n`.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::rigid;
+/// let rotations = rigid::rotations3(24);
+/// assert_eq!(24, rotations.len());
+/// ```
+pub fn rotations3(n: usize) -> Vec<SquareMatrix<U3>> {
+    let phi = 2f64.sqrt();
+    let psi = 1.533_751_168_755_204_3;
+    (0..n)
+        .map(|i| {
+            let s = i as f64 + 0.5;
+            let t = s / n as f64;
+            let r = t.sqrt();
+            let big_r = (1. - t).sqrt();
+            let alpha = 2. * PI * s / phi;
+            let beta = 2. * PI * s / psi;
+            let quaternion = UnitQuaternion::from_quaternion(Quaternion::new(
+                big_r * beta.cos(),
+                r * alpha.sin(),
+                r * alpha.cos(),
+                big_r * beta.sin(),
+            ));
+            *quaternion.to_rotation_matrix().matrix()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rigid;
+    use utils;
+
+    #[test]
+    fn ranked() {
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let rotation = Rotation2::new(2.5);
+        let moving = fixed.clone() * rotation;
+        let multi_start = Rigid::new()
+            .register_multi_start(&fixed, &moving, &rotations2(8))
+            .unwrap();
+        assert_eq!(8, multi_start.runs.len());
+        for pair in multi_start.errors.windows(2) {
+            assert!(pair[0] <= pair[1] || !pair[1].is_finite());
+        }
+        let best = multi_start.best().unwrap();
+        assert_relative_eq!(*rotation.matrix(), best.transform.rotation, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn cancelled_ranked_last() {
+        use runner::Cancel;
+        use Runner;
+
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let rotation = Rotation2::new(0.5);
+        let moving = fixed.clone() * rotation;
+        let initial = centroid_transform(&fixed, &moving, rotation.matrix());
+        let cancel = Cancel::new();
+        cancel.cancel();
+        let cancelled = Runner::new()
+            .cancel(cancel)
+            .rigid()
+            .register_from(&fixed, &moving, &initial)
+            .unwrap();
+        assert_eq!(0, cancelled.iterations);
+        let run = Rigid::new()
+            .register_from(&fixed, &moving, &Transform::identity())
+            .unwrap();
+        let multi_start = MultiStart::new(&fixed, 0.1, vec![cancelled, run], Vec::new()).unwrap();
+        assert!(multi_start.errors[0].is_finite());
+        assert!(multi_start.errors[1].is_nan());
+        assert!(multi_start.best().unwrap().iterations > 0);
+    }
+
+    #[test]
+    fn failed_starts_are_skipped() {
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let rotations = vec![
+            SquareMatrix::<U2>::from_element(f64::NAN),
+            SquareMatrix::<U2>::identity(),
+        ];
+        let multi_start = Rigid::new()
+            .register_multi_start(&fixed, &fixed, &rotations)
+            .unwrap();
+        assert_eq!(1, multi_start.runs.len());
+        assert_eq!(1, multi_start.failures.len());
+        assert_eq!(0, multi_start.failures[0].0);
+    }
+
+    #[test]
+    fn every_start_failed() {
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let rotations = vec![SquareMatrix::<U2>::from_element(f64::NAN); 2];
+        assert!(
+            Rigid::new()
+                .register_multi_start(&fixed, &fixed, &rotations)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rotations3_are_rotations() {
+        for rotation in rotations3(10) {
+            assert_relative_eq!(1., rotation.determinant(), epsilon = 1e-8);
+            assert_relative_eq!(
+                SquareMatrix::<U3>::identity(),
+                rotation.transpose() * rotation,
+                epsilon = 1e-8
+            );
+        }
+    }
+}
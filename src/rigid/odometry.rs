@@ -0,0 +1,96 @@
+use UInt;
+use generic_array::ArrayLength;
+use nalgebra::DimName;
+use rigid::Transform;
+use runner::Termination;
+use std::ops::Mul;
+use std::time::Duration;
+
+/// The result of registering a sequence of point sets, each to the one before it.
+#[derive(Debug)]
+pub struct Odometry<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// The global pose of each point set, mapping it into the frame of the first point set.
+    ///
+    /// The first pose is always the identity.
+    pub poses: Vec<Transform<D>>,
+
+    /// Statistics for each registration between consecutive point sets.
+    ///
+    /// There is one fewer step than there are poses.
+    pub steps: Vec<Step<D>>,
+}
+
+/// Statistics for one registration in a sequence.
+#[derive(Clone, Debug)]
+pub struct Step<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// The accumulated residual up to and including this step, in the units of the input points.
+    ///
+    /// This is the sum of the square roots of each step's denormalized sigma2, i.e. of each
+    /// registration's residual noise. It grows with how poorly consecutive point sets fit
+    /// together, but it is not an estimate of the uncertainty of the accumulated pose.
+    pub accumulated_residual: f64,
+
+    /// Did this registration converge?
+    pub converged: bool,
+
+    /// The final sigma2, in the units of the input points.
+    pub denormalized_sigma2: f64,
+
+    /// The number of iterations.
+    pub iterations: usize,
+
+    /// The motion between the two point sets, mapping the later point set into the frame of the
+    /// earlier one.
+    pub motion: Transform<D>,
+
+    /// The wall-clock duration of this registration.
+    pub runtime: Duration,
+
+    /// Why this registration stopped iterating.
+    pub termination: Termination,
+}
+
+#[cfg(test)]
+mod tests {
+    use {Rigid, Vector, U2};
+
+    #[test]
+    fn accumulates() {
+        let first = ::utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let frames = (0..4).map(|i| {
+            let mut frame = first.clone();
+            frame.column_mut(0).add_scalar_mut(-(i as f64));
+            frame
+        });
+        let odometry = Rigid::new().register_sequence(frames).unwrap();
+        assert_eq!(4, odometry.poses.len());
+        for step in &odometry.steps {
+            assert!(step.converged);
+            assert_relative_eq!(
+                Vector::<U2>::new(1., 0.),
+                step.motion.translation,
+                epsilon = 1e-6
+            );
+        }
+        for pair in odometry.steps.windows(2) {
+            assert!(pair[0].accumulated_residual <= pair[1].accumulated_residual);
+        }
+        assert_relative_eq!(
+            Vector::<U2>::new(3., 0.),
+            odometry.poses[3].translation,
+            epsilon = 1e-6
+        );
+    }
+}
@@ -12,7 +12,7 @@ use std::ops::Mul;
 pub struct CannotNormalizeIndependentlyWithoutScale;
 
 /// A `Registration` for running rigid registrations.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Registration<'a, D>
 where
     D: DimName,
@@ -132,6 +132,23 @@ where
         moved
     }
 
+    fn set_transform(&mut self, transform: &Transform<D>) {
+        self.rotation = transform.rotation.clone();
+        self.scale = if self.rigid.scale {
+            transform.scale.unwrap_or(1.)
+        } else {
+            1.
+        };
+        self.translation = transform.translation.clone();
+    }
+
+    fn normalize(&mut self, normalization: &Normalization<D>) {
+        self.translation = (&self.translation - &normalization.fixed.offset
+            + self.scale * &self.rotation * &normalization.moving.offset)
+            / normalization.fixed.scale;
+        self.scale *= normalization.moving.scale / normalization.fixed.scale;
+    }
+
     fn denormalize(&mut self, normalization: &Normalization<D>) {
         self.scale *= normalization.fixed.scale / normalization.moving.scale;
         self.translation = normalization.fixed.scale * &self.translation
@@ -166,6 +183,29 @@ mod tests {
     use {Normalize, Runner};
     use nalgebra::U2;
 
+    #[test]
+    fn normalize_and_denormalize() {
+        use utils;
+        use Registration as RegistrationTrait;
+
+        let fixed = utils::random_matrix2(10);
+        let moving = utils::random_matrix2(10) * 3.;
+        let (_, _, normalization) = Normalize::Independent.normalize(&fixed, &moving);
+        let normalization = normalization.unwrap();
+        let rigid = Rigid::new().scale(true);
+        let transform = Transform {
+            rotation: *::nalgebra::Rotation2::new(0.3).matrix(),
+            scale: Some(1.5),
+            translation: Vector::<U2>::new(1., -2.),
+        };
+        let mut registration = Registration::<U2>::new(&rigid).unwrap();
+        registration.set_transform(&transform);
+        let expected = registration.transform(&moving);
+        registration.normalize(&normalization);
+        registration.denormalize(&normalization);
+        assert_relative_eq!(expected, registration.transform(&moving), epsilon = 1e-8);
+    }
+
     #[test]
     fn normalize_independent_and_no_scale() {
         let rigid = Runner::new()
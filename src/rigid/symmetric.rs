@@ -0,0 +1,160 @@
+use {Matrix, PointTransform, Run, SquareMatrix, UInt};
+use generic_array::ArrayLength;
+use nalgebra::{DefaultAllocator, DimMin, DimName, DimSub, U1};
+use nalgebra::allocator::Allocator;
+use rigid::Transform;
+use std::f64;
+use std::ops::Mul;
+
+/// The result of a symmetric (bidirectional) rigid registration.
+#[derive(Debug)]
+pub struct Symmetric<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// The run that registers the moving points onto the fixed points.
+    pub forward: Run<D, Transform<D>>,
+
+    /// The run that registers the fixed points onto the moving points.
+    pub backward: Run<D, Transform<D>>,
+
+    /// How well the forward transform agrees with the inverse of the backward transform.
+    pub consistency: Consistency,
+}
+
+/// Measures of the agreement between a forward transform and the inverse of a backward transform.
+///
+/// Large values are a cheap signal that one of the registrations landed in a wrong local minimum.
+/// Every measure is infinite if the backward transform is not invertible.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Consistency {
+    /// The angle of the rotation between the two transforms' rotations, in radians.
+    pub rotation: f64,
+
+    /// The root mean square distance between the moving points as moved by each transform, in the
+    /// units of the fixed points.
+    pub rms: f64,
+
+    /// The absolute difference between the two transforms' scales.
+    pub scale: f64,
+
+    /// The distance between the two transforms' translations.
+    pub translation: f64,
+}
+
+impl<D> Symmetric<D>
+where
+    D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    DefaultAllocator: Allocator<f64, D, D>
+        + Allocator<(usize, usize), D>
+        + Allocator<f64, <D as DimSub<U1>>::Output>,
+{
+    pub(crate) fn new(
+        moving: &Matrix<D>,
+        forward: Run<D, Transform<D>>,
+        backward: Run<D, Transform<D>>,
+    ) -> Symmetric<D> {
+        let consistency = Consistency::new(moving, &forward.transform, &backward.transform);
+        Symmetric {
+            backward: backward,
+            consistency: consistency,
+            forward: forward,
+        }
+    }
+
+    /// Returns the average of the forward transform and the inverse of the backward transform.
+    ///
+    /// The rotation is the projection of the mean rotation matrix back onto the rotations, the
+    /// scale is the geometric mean of the scales, and the translation is the mean translation.
+    ///
+    /// Returns `None` if the backward transform is not invertible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Rigid, utils};
+    /// let fixed = utils::random_matrix2(10);
+    /// let moving = utils::random_matrix2(10);
+    /// let symmetric = Rigid::new().register_symmetric(&fixed, &moving).unwrap();
+    /// let transform = symmetric.average().unwrap();
+    /// ```
+    pub fn average(&self) -> Option<Transform<D>> {
+        let forward = &self.forward.transform;
+        let backward = self.backward.transform.inverse()?;
+        let svd = ((&forward.rotation + &backward.rotation) / 2.).svd(true, true);
+        let u = svd.u.unwrap();
+        let v_t = svd.v_t.unwrap();
+        let mut c = SquareMatrix::<D>::identity();
+        let forward_determinant = forward.rotation.determinant();
+        if (&u * &v_t).determinant() * forward_determinant < 0. {
+            c[(D::dim() - 1, D::dim() - 1)] = -1.;
+        }
+        let scale = match (forward.scale, backward.scale) {
+            (None, None) => None,
+            (a, b) => Some((a.unwrap_or(1.) * b.unwrap_or(1.)).sqrt()),
+        };
+        Some(Transform {
+            rotation: u * c * v_t,
+            scale: scale,
+            translation: (&forward.translation + &backward.translation) / 2.,
+        })
+    }
+}
+
+impl Consistency {
+    fn new<D>(moving: &Matrix<D>, forward: &Transform<D>, backward: &Transform<D>) -> Consistency
+    where
+        D: DimName,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
+        let backward = match backward.inverse() {
+            Some(backward) => backward,
+            None => {
+                return Consistency {
+                    rotation: f64::INFINITY,
+                    rms: f64::INFINITY,
+                    scale: f64::INFINITY,
+                    translation: f64::INFINITY,
+                }
+            }
+        };
+        let difference = forward.rotation.transpose() * &backward.rotation;
+        let cos = (difference.trace() - (D::dim() as f64 - 2.)) / 2.;
+        let moved = forward.apply(moving) - backward.apply(moving);
+        let rms = (moved.iter().map(|n| n.powi(2)).sum::<f64>() / moving.nrows() as f64).sqrt();
+        Consistency {
+            rotation: cos.max(-1.).min(1.).acos(),
+            rms: rms,
+            scale: (forward.scale.unwrap_or(1.) - backward.scale.unwrap_or(1.)).abs(),
+            translation: (&forward.translation - &backward.translation).norm(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Rigid;
+    use nalgebra::Rotation2;
+    use utils;
+
+    #[test]
+    fn consistent() {
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let rotation = Rotation2::new(0.5);
+        let moving = fixed.clone() * rotation;
+        let symmetric = Rigid::new().register_symmetric(&fixed, &moving).unwrap();
+        assert!(symmetric.consistency.rotation < 1e-6);
+        assert!(symmetric.consistency.translation < 1e-6);
+        assert!(symmetric.consistency.rms < 1e-6);
+        let average = symmetric.average().unwrap();
+        assert_relative_eq!(*rotation.matrix(), average.rotation, epsilon = 1e-6);
+    }
+}
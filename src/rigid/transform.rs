@@ -1,10 +1,24 @@
-use {SquareMatrix, UInt, Vector};
+use {Matrix, PointTransform, SquareMatrix, UInt, Vector};
 use generic_array::ArrayLength;
-use nalgebra::{DimName, Rotation3, Transform3, Translation3, U3};
+use nalgebra::{Affine2, Affine3, DMatrix, DimName, Isometry2, Isometry3, Matrix3, Matrix4,
+               Rotation2, Rotation3, Similarity2, Similarity3, Transform3, Translation2,
+               Translation3, U2, U3, Unit, UnitComplex, UnitQuaternion};
+use std::f64;
 use std::ops::Mul;
 
 /// The result of a rigid transform.
-#[derive(Debug)]
+///
+/// A transform maps moving coordinates into the fixed frame, i.e. `x_fixed = s·R·x_moving + t`.
+/// Every conversion and file format keeps this direction, including the PDAL, CloudCompare, and
+/// JSON readers and writers, so apply a transform to the moving point set to bring it onto the
+/// fixed point set.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde",
+           serde(bound(serialize = "SquareMatrix<D>: ::serde::Serialize, \
+                                    Vector<D>: ::serde::Serialize",
+                       deserialize = "SquareMatrix<D>: ::serde::Deserialize<'de>, \
+                                      Vector<D>: ::serde::Deserialize<'de>")))]
 pub struct Transform<D>
 where
     D: DimName,
@@ -22,31 +36,605 @@ where
     pub translation: Vector<D>,
 }
 
+impl<D> Transform<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Creates an identity transform, with no scaling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let transform = Transform::<U2>::identity();
+    /// assert_eq!(None, transform.scale);
+    /// ```
+    pub fn identity() -> Transform<D> {
+        Transform {
+            rotation: SquareMatrix::<D>::identity(),
+            scale: None,
+            translation: Vector::<D>::zeros(),
+        }
+    }
+
+    fn scales(&self) -> bool {
+        self.scale.map_or(false, |scale| (scale - 1.).abs() > f64::EPSILON)
+    }
+}
+
+impl<D> PointTransform<D> for Transform<D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Applies this transform to a matrix of points, i.e. `s·R·x + t` for each point `x`.
+    fn apply(&self, matrix: &Matrix<D>) -> Matrix<D> {
+        let mut moved = self.scale.unwrap_or(1.) * matrix * self.rotation.transpose();
+        for d in 0..D::dim() {
+            moved.column_mut(d).add_scalar_mut(self.translation[d]);
+        }
+        moved
+    }
+
+    fn compose(&self, other: &Transform<D>) -> Transform<D> {
+        let scale = match (self.scale, other.scale) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(1.) * b.unwrap_or(1.)),
+        };
+        Transform {
+            rotation: &self.rotation * &other.rotation,
+            scale: scale,
+            translation: self.scale.unwrap_or(1.) * &self.rotation * &other.translation
+                + &self.translation,
+        }
+    }
+
+    /// Returns the inverse of this transform, or `None` if its scale is zero or not finite.
+    fn inverse(&self) -> Option<Transform<D>> {
+        let scale = self.scale.unwrap_or(1.);
+        if scale == 0. || !scale.is_finite() {
+            return None;
+        }
+        let rotation = self.rotation.transpose();
+        let translation = -(&rotation * &self.translation) / scale;
+        Some(Transform {
+            rotation: rotation,
+            scale: self.scale.map(|scale| 1. / scale),
+            translation: translation,
+        })
+    }
+
+    fn to_homogeneous(&self) -> DMatrix<f64> {
+        let dim = D::dim();
+        let scale = self.scale.unwrap_or(1.);
+        DMatrix::from_fn(dim + 1, dim + 1, |i, j| match (i < dim, j < dim) {
+            (true, true) => scale * self.rotation[(i, j)],
+            (true, false) => self.translation[i],
+            (false, true) => 0.,
+            (false, false) => 1.,
+        })
+    }
+}
+
+impl Transform<U2> {
+    /// Creates a transform that rotates counterclockwise by `angle` radians, with no translation
+    /// or scaling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let transform = Transform::<U2>::from_angle(0.5);
+    /// assert_eq!(Some(0.5), transform.angle());
+    /// ```
+    pub fn from_angle(angle: f64) -> Transform<U2> {
+        Transform {
+            rotation: *Rotation2::new(angle).matrix(),
+            ..Transform::identity()
+        }
+    }
+
+    /// Returns the counterclockwise rotation angle in radians, in `(-π, π]`.
+    ///
+    /// Returns none if the rotation is a reflection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// assert_eq!(Some(0.), Transform::<U2>::identity().angle());
+    /// ```
+    pub fn angle(&self) -> Option<f64> {
+        self.unit_complex().map(|rotation| rotation.angle())
+    }
+
+    /// Converts a two-dimensional transform to a Similarity2.
+    ///
+    /// Returns none if the rotation is a reflection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let similarity = Transform::<U2>::identity().to_similarity2().unwrap();
+    /// assert_eq!(1., similarity.scaling());
+    /// ```
+    pub fn to_similarity2(&self) -> Option<Similarity2<f64>> {
+        self.unit_complex().map(|rotation| {
+            Similarity2::from_parts(
+                Translation2::from(self.translation),
+                rotation,
+                self.scale.unwrap_or(1.),
+            )
+        })
+    }
+
+    /// Converts a two-dimensional transform to an Isometry2.
+    ///
+    /// Returns none if the rotation is a reflection or if the transform scales.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let mut transform = Transform::<U2>::identity();
+    /// assert!(transform.to_isometry2().is_some());
+    /// transform.scale = Some(2.);
+    /// assert!(transform.to_isometry2().is_none());
+    /// ```
+    pub fn to_isometry2(&self) -> Option<Isometry2<f64>> {
+        if self.scales() {
+            return None;
+        }
+        self.unit_complex().map(|rotation| {
+            Isometry2::from_parts(Translation2::from(self.translation), rotation)
+        })
+    }
+
+    /// Converts a two-dimensional transform to an Affine2.
+    ///
+    /// Unlike the other conversions, this conversion always succeeds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U2;
+    /// use cpd::rigid::Transform;
+    /// let affine = Transform::<U2>::identity().to_affine2();
+    /// ```
+    pub fn to_affine2(&self) -> Affine2<f64> {
+        let homogeneous = self.to_homogeneous();
+        Affine2::from_matrix_unchecked(Matrix3::from_fn(|i, j| homogeneous[(i, j)]))
+    }
+
+    fn unit_complex(&self) -> Option<UnitComplex<f64>> {
+        if self.rotation.determinant() < 0. {
+            None
+        } else {
+            Some(UnitComplex::from_rotation_matrix(
+                &Rotation2::from_matrix_unchecked(self.rotation),
+            ))
+        }
+    }
+}
+
 impl Transform<U3> {
+    /// Creates a transform from yaw, pitch, and roll angles in radians, with no translation or
+    /// scaling.
+    ///
+    /// The convention is intrinsic Z-Y'-X'', i.e. the rotation matrix is
+    /// `Rz(yaw)·Ry(pitch)·Rx(roll)`: points are rotated first about x by roll, then about y by
+    /// pitch, then about z by yaw.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{U3, Vector};
+    /// use cpd::rigid::Transform;
+    /// let mut transform = Transform::<U3>::from_yaw_pitch_roll(0.3, 0.2, 0.1);
+    /// transform.translation = Vector::<U3>::new(1., 2., 3.);
+    /// ```
+    pub fn from_yaw_pitch_roll(yaw: f64, pitch: f64, roll: f64) -> Transform<U3> {
+        Transform {
+            rotation: *Rotation3::from_euler_angles(roll, pitch, yaw).matrix(),
+            ..Transform::identity()
+        }
+    }
+
+    /// Creates a transform that rotates by `angle` radians about `axis`, with no translation or
+    /// scaling.
+    ///
+    /// The axis does not have to be normalized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{U3, Vector};
+    /// use cpd::rigid::Transform;
+    /// let transform = Transform::<U3>::from_axis_angle(&Vector::<U3>::z(), 0.5);
+    /// ```
+    pub fn from_axis_angle(axis: &Vector<U3>, angle: f64) -> Transform<U3> {
+        Transform {
+            rotation: *Rotation3::from_axis_angle(&Unit::new_normalize(*axis), angle).matrix(),
+            ..Transform::identity()
+        }
+    }
+
+    /// Creates a transform from a unit quaternion, with no translation or scaling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate cpd;
+    /// extern crate nalgebra;
+    /// # fn main() {
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// use nalgebra::UnitQuaternion;
+    /// let transform = Transform::<U3>::from_quaternion(&UnitQuaternion::identity());
+    /// assert_eq!(Transform::identity(), transform);
+    /// # }
+    /// ```
+    pub fn from_quaternion(quaternion: &UnitQuaternion<f64>) -> Transform<U3> {
+        Transform {
+            rotation: *quaternion.to_rotation_matrix().matrix(),
+            ..Transform::identity()
+        }
+    }
+
+    /// Returns the yaw, pitch, and roll angles of the rotation, in radians.
+    ///
+    /// See `from_yaw_pitch_roll` for the convention. Returns none if the rotation is a reflection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let transform = Transform::<U3>::from_yaw_pitch_roll(0.3, 0.2, 0.1);
+    /// let (yaw, pitch, roll) = transform.yaw_pitch_roll().unwrap();
+    /// assert!((yaw - 0.3).abs() < 1e-12);
+    /// ```
+    pub fn yaw_pitch_roll(&self) -> Option<(f64, f64, f64)> {
+        self.rotation3().map(|rotation| {
+            let (roll, pitch, yaw) = rotation.euler_angles();
+            (yaw, pitch, roll)
+        })
+    }
+
+    /// Returns the unit axis and the angle, in radians, of the rotation.
+    ///
+    /// Returns none if the rotation is the identity (the axis is undefined) or a reflection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{U3, Vector};
+    /// use cpd::rigid::Transform;
+    /// let transform = Transform::<U3>::from_axis_angle(&Vector::<U3>::z(), 0.5);
+    /// let (axis, angle) = transform.axis_angle().unwrap();
+    /// assert!((axis.z - 1.).abs() < 1e-12);
+    /// assert!((angle - 0.5).abs() < 1e-12);
+    /// assert!(Transform::<U3>::identity().axis_angle().is_none());
+    /// ```
+    pub fn axis_angle(&self) -> Option<(Vector<U3>, f64)> {
+        self.rotation3()
+            .and_then(|rotation| rotation.axis_angle())
+            .map(|(axis, angle)| (axis.into_inner(), angle))
+    }
+
+    /// Returns the rotation as a unit quaternion.
+    ///
+    /// Returns none if the rotation is a reflection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let quaternion = Transform::<U3>::identity().quaternion().unwrap();
+    /// assert_eq!(0., quaternion.angle());
+    /// ```
+    pub fn quaternion(&self) -> Option<UnitQuaternion<f64>> {
+        self.rotation3()
+            .map(|rotation| UnitQuaternion::from_rotation_matrix(&rotation))
+    }
+
     /// Converts a three-dimensional transform to a Transform3.
+    ///
+    /// The result moves points exactly like `apply`, i.e. `s·R·x + t`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate cpd;
+    /// extern crate nalgebra;
+    /// # fn main() {
+    /// use cpd::{U3, Vector};
+    /// use cpd::rigid::Transform;
+    /// use nalgebra::Point3;
+    /// let mut transform = Transform::<U3>::identity();
+    /// transform.translation = Vector::<U3>::new(1., 2., 3.);
+    /// let transform3 = transform.as_transform3();
+    /// assert_eq!(Point3::new(1., 2., 3.), transform3 * Point3::origin());
+    /// # }
+    /// ```
     pub fn as_transform3(&self) -> Transform3<f64> {
-        use alga::general::SubsetOf;
-        let rotation = Rotation3::from_matrix_unchecked(self.rotation);
-        let transform: Transform3<f64> = rotation.to_superset();
-        transform * Translation3::from_vector(self.translation)
+        Transform3::from_matrix_unchecked(self.homogeneous3())
+    }
+
+    /// Converts a three-dimensional transform to a Similarity3.
+    ///
+    /// Returns none if the rotation is a reflection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let similarity = Transform::<U3>::identity().to_similarity3().unwrap();
+    /// assert_eq!(1., similarity.scaling());
+    /// ```
+    pub fn to_similarity3(&self) -> Option<Similarity3<f64>> {
+        self.quaternion().map(|rotation| {
+            Similarity3::from_parts(
+                Translation3::from(self.translation),
+                rotation,
+                self.scale.unwrap_or(1.),
+            )
+        })
+    }
+
+    /// Converts a three-dimensional transform to an Isometry3.
+    ///
+    /// Returns none if the rotation is a reflection or if the transform scales.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let mut transform = Transform::<U3>::identity();
+    /// assert!(transform.to_isometry3().is_some());
+    /// transform.scale = Some(2.);
+    /// assert!(transform.to_isometry3().is_none());
+    /// ```
+    pub fn to_isometry3(&self) -> Option<Isometry3<f64>> {
+        if self.scales() {
+            return None;
+        }
+        self.quaternion().map(|rotation| {
+            Isometry3::from_parts(Translation3::from(self.translation), rotation)
+        })
+    }
+
+    /// Converts a three-dimensional transform to an Affine3.
+    ///
+    /// Unlike the other conversions, this conversion always succeeds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::U3;
+    /// use cpd::rigid::Transform;
+    /// let affine = Transform::<U3>::identity().to_affine3();
+    /// ```
+    pub fn to_affine3(&self) -> Affine3<f64> {
+        Affine3::from_matrix_unchecked(self.homogeneous3())
+    }
+
+    pub(crate) fn homogeneous3(&self) -> Matrix4<f64> {
+        let homogeneous = self.to_homogeneous();
+        Matrix4::from_fn(|i, j| homogeneous[(i, j)])
+    }
+
+    fn rotation3(&self) -> Option<Rotation3<f64>> {
+        if self.rotation.determinant() < 0. {
+            None
+        } else {
+            Some(Rotation3::from_matrix_unchecked(self.rotation))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nalgebra::{DefaultAllocator, DimMin, DimSub, Point2, Point3, U1};
+    use nalgebra::allocator::Allocator;
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn as_transform3() {
+    fn serde() {
+        use serde_json;
         let transform = Transform {
             rotation: SquareMatrix::<U3>::identity(),
-            scale: None,
+            scale: Some(2.),
             translation: Vector::<U3>::new(1., 2., 3.),
         };
-        let transform = transform.as_transform3();
-        assert_eq!(1., transform.matrix()[(0, 0)]);
-        assert_eq!(1., transform.matrix()[(0, 3)]);
-        assert_eq!(2., transform.matrix()[(1, 3)]);
-        assert_eq!(3., transform.matrix()[(2, 3)]);
+        let json = serde_json::to_string(&transform).unwrap();
+        assert_eq!(transform, serde_json::from_str(&json).unwrap());
+    }
+
+    fn transform() -> Transform<U3> {
+        Transform {
+            rotation: *Rotation3::from_euler_angles(0.1, 0.2, 0.3).matrix(),
+            scale: Some(2.),
+            translation: Vector::<U3>::new(1., 2., 3.),
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let transform = transform();
+        let matrix = Matrix::<U3>::new_random(10);
+        assert_relative_eq!(
+            matrix,
+            transform.inverse().unwrap().apply(&transform.apply(&matrix)),
+            epsilon = 1e-8
+        );
+    }
+
+    #[test]
+    fn compose() {
+        let transform = transform();
+        let matrix = Matrix::<U3>::new_random(10);
+        let mut other = Transform::<U3>::identity();
+        other.translation = Vector::<U3>::new(-1., 0., 1.);
+        assert_relative_eq!(
+            transform.apply(&other.apply(&matrix)),
+            transform.compose(&other).apply(&matrix),
+            epsilon = 1e-8
+        );
+    }
+
+    #[test]
+    fn to_homogeneous() {
+        use nalgebra::DVector;
+        let transform = transform();
+        let matrix = Matrix::<U3>::new_random(10);
+        let homogeneous = transform.to_homogeneous();
+        let moved = transform.apply(&matrix);
+        for i in 0..matrix.nrows() {
+            let row = matrix.row(i).iter().cloned().chain(Some(1.)).collect::<Vec<_>>();
+            let point = &homogeneous * DVector::from_iterator(4, row);
+            assert_relative_eq!(moved[(i, 0)], point[0], epsilon = 1e-8);
+            assert_relative_eq!(moved[(i, 1)], point[1], epsilon = 1e-8);
+            assert_relative_eq!(moved[(i, 2)], point[2], epsilon = 1e-8);
+            assert_eq!(1., point[3]);
+        }
+    }
+
+    fn registration_transform<D>(transform: &Transform<D>, matrix: &Matrix<D>) -> Matrix<D>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        use {Registration, Rigid};
+        let rigid = Rigid::new().scale(true);
+        let mut registration = rigid.as_registration().unwrap();
+        registration.set_transform(transform);
+        registration.transform(matrix)
+    }
+
+    #[test]
+    fn as_transform3() {
+        let transform = transform();
+        let matrix = Matrix::<U3>::new_random(10);
+        let expected = registration_transform(&transform, &matrix);
+        let transform3 = transform.as_transform3();
+        let affine3 = transform.to_affine3();
+        let similarity3 = transform.to_similarity3().unwrap();
+        for i in 0..matrix.nrows() {
+            let point = Point3::from(matrix.row(i).transpose());
+            let expected = Point3::from(expected.row(i).transpose());
+            assert_relative_eq!(expected, transform3 * point, epsilon = 1e-8);
+            assert_relative_eq!(expected, affine3 * point, epsilon = 1e-8);
+            assert_relative_eq!(expected, similarity3 * point, epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn to_isometry3() {
+        let mut transform = transform();
+        assert!(transform.to_isometry3().is_none());
+        transform.scale = None;
+        let matrix = Matrix::<U3>::new_random(10);
+        let expected = registration_transform(&transform, &matrix);
+        let isometry3 = transform.to_isometry3().unwrap();
+        for i in 0..matrix.nrows() {
+            let point = Point3::from(matrix.row(i).transpose());
+            let expected = Point3::from(expected.row(i).transpose());
+            assert_relative_eq!(expected, isometry3 * point, epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn to_similarity2() {
+        let transform = Transform {
+            rotation: *Rotation2::new(0.3).matrix(),
+            scale: Some(0.5),
+            translation: Vector::<U2>::new(1., 2.),
+        };
+        let matrix = Matrix::<U2>::new_random(10);
+        let expected = registration_transform(&transform, &matrix);
+        let similarity2 = transform.to_similarity2().unwrap();
+        let affine2 = transform.to_affine2();
+        let isometry2 = Transform {
+            scale: None,
+            ..transform.clone()
+        }.to_isometry2()
+            .unwrap();
+        for i in 0..matrix.nrows() {
+            let point = Point2::from(matrix.row(i).transpose());
+            let expected = Point2::from(expected.row(i).transpose());
+            assert_relative_eq!(expected, similarity2 * point, epsilon = 1e-8);
+            assert_relative_eq!(expected, affine2 * point, epsilon = 1e-8);
+            assert_relative_eq!(
+                transform.rotation * point.coords + transform.translation,
+                (isometry2 * point).coords,
+                epsilon = 1e-8
+            );
+        }
+    }
+
+    #[test]
+    fn yaw_pitch_roll() {
+        let transform = Transform::<U3>::from_yaw_pitch_roll(0.3, 0.2, 0.1);
+        let (yaw, pitch, roll) = transform.yaw_pitch_roll().unwrap();
+        assert_relative_eq!(0.3, yaw, epsilon = 1e-12);
+        assert_relative_eq!(0.2, pitch, epsilon = 1e-12);
+        assert_relative_eq!(0.1, roll, epsilon = 1e-12);
+        let expected = Rotation3::from_axis_angle(&Vector::<U3>::z_axis(), 0.3)
+            * Rotation3::from_axis_angle(&Vector::<U3>::y_axis(), 0.2)
+            * Rotation3::from_axis_angle(&Vector::<U3>::x_axis(), 0.1);
+        assert_relative_eq!(*expected.matrix(), transform.rotation, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn axis_angle_and_quaternion() {
+        let axis = Vector::<U3>::new(1., 2., 3.);
+        let transform = Transform::<U3>::from_axis_angle(&axis, 0.4);
+        let (unit, angle) = transform.axis_angle().unwrap();
+        assert_relative_eq!(axis.normalize(), unit, epsilon = 1e-12);
+        assert_relative_eq!(0.4, angle, epsilon = 1e-12);
+        let quaternion = transform.quaternion().unwrap();
+        assert_relative_eq!(
+            transform.rotation,
+            Transform::<U3>::from_quaternion(&quaternion).rotation,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn angle() {
+        let transform = Transform::<U2>::from_angle(-2.);
+        assert_relative_eq!(-2., transform.angle().unwrap(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn reflection() {
+        let mut transform = Transform::<U2>::identity();
+        transform.rotation[(0, 0)] = -1.;
+        assert!(transform.to_similarity2().is_none());
+        assert!(transform.to_isometry2().is_none());
+        assert!(transform.angle().is_none());
     }
 }
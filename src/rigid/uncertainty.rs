@@ -0,0 +1,300 @@
+use {Matrix, Rigid, Run, UInt};
+use failure::Error;
+use gauss_transform::Transformer;
+use generic_array::ArrayLength;
+use nalgebra::{DMatrix, DVector, DefaultAllocator, DimMin, DimName, DimSub, U1};
+use nalgebra::allocator::Allocator;
+use rigid::Transform;
+use std::ops::Mul;
+
+/// Strategies for estimating the covariance of the rigid transform parameters.
+///
+/// Set a strategy with `Rigid::uncertainty` to fill `Run::covariance`, or pass one to
+/// `Rigid::covariance` to estimate the covariance of an existing run. The covariance is in the
+/// units of the input points, over the parameter vector `[rotation, translation, scale]`:
+///
+/// - **rotation**: `D(D-1)/2` small rotations (radians) applied after the estimated rotation. In
+/// two dimensions this is the counterclockwise angle. In three dimensions these are rotations
+/// about the x, y, and z axes, in that order.
+/// - **translation**: `D` values, one per axis.
+/// - **scale**: one value, only present if the registration scales.
+///
+/// ```
+/// use cpd::rigid::Uncertainty;
+/// let uncertainty = Uncertainty::Bootstrap { samples: 100, seed: 42 };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Uncertainty {
+    /// Invert the observed information at convergence.
+    ///
+    /// Uses the run's final correspondence probabilities and sigma2, treating the correspondences
+    /// as known. This is cheap, but tends to underestimate the covariance when correspondences are
+    /// ambiguous.
+    ObservedInformation,
+
+    /// Re-register many resamplings (with replacement) of the moving points, and use the sample
+    /// covariance of the resulting parameters.
+    ///
+    /// This is expensive, since it runs one registration per sample, but makes fewer assumptions.
+    Bootstrap {
+        /// The number of resamplings.
+        samples: usize,
+
+        /// The seed for the resampling's random number generator, so results are repeatable.
+        seed: u64,
+    },
+}
+
+/// A xorshift64 random number generator, which is plenty for resampling.
+#[derive(Debug)]
+struct XorShift(u64);
+
+impl Uncertainty {
+    pub(crate) fn covariance<D>(
+        &self,
+        rigid: &Rigid,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        run: &Run<D, Transform<D>>,
+    ) -> Result<Option<DMatrix<f64>>, Error>
+    where
+        D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+        DefaultAllocator: Allocator<f64, D, D>
+            + Allocator<(usize, usize), D>
+            + Allocator<f64, <D as DimSub<U1>>::Output>,
+    {
+        match *self {
+            Uncertainty::ObservedInformation => observed_information(rigid, fixed, moving, run),
+            Uncertainty::Bootstrap { samples, seed } => {
+                bootstrap(rigid, fixed, moving, run, samples, seed)
+            }
+        }
+    }
+}
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        XorShift(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+fn observed_information<D>(
+    rigid: &Rigid,
+    fixed: &Matrix<D>,
+    moving: &Matrix<D>,
+    run: &Run<D, Transform<D>>,
+) -> Result<Option<DMatrix<f64>>, Error>
+where
+    D: DimName,
+    UInt: Mul<<D as DimName>::Value>,
+    <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    if !(run.denormalized_sigma2.is_finite() && run.denormalized_sigma2 > 0.) {
+        warn!("The sigma2 has collapsed, no covariance can be estimated");
+        return Ok(None);
+    }
+    let p1 = match run.p1 {
+        Some(ref p1) if p1.len() == moving.nrows() => p1.clone(),
+        _ => {
+            let transformer = Transformer::new(fixed, rigid.runner.outlier_weight)?;
+            transformer
+                .probabilities(&run.moved, run.denormalized_sigma2)
+                .p1
+        }
+    };
+    let scale = run.transform.scale.unwrap_or(1.);
+    let rotated = moving * run.transform.rotation.transpose();
+    let planes = planes(D::dim());
+    let size = parameters(D::dim(), rigid.scale);
+    let mut information = DMatrix::<f64>::zeros(size, size);
+    for m in 0..rotated.nrows() {
+        let mut jacobian = DMatrix::<f64>::zeros(D::dim(), size);
+        for (k, &(a, b)) in planes.iter().enumerate() {
+            jacobian[(b, k)] = scale * rotated[(m, a)];
+            jacobian[(a, k)] = -scale * rotated[(m, b)];
+        }
+        for d in 0..D::dim() {
+            jacobian[(d, planes.len() + d)] = 1.;
+            if rigid.scale {
+                jacobian[(d, size - 1)] = rotated[(m, d)];
+            }
+        }
+        information += p1[m] * jacobian.transpose() * jacobian;
+    }
+    information /= run.denormalized_sigma2;
+    if information.iter().any(|n| !n.is_finite()) {
+        warn!("The observed information is not finite, no covariance can be estimated");
+        return Ok(None);
+    }
+    let covariance = information.try_inverse();
+    if covariance.is_none() {
+        warn!("The observed information is singular, no covariance can be estimated");
+    }
+    Ok(covariance)
+}
+
+fn bootstrap<D>(
+    rigid: &Rigid,
+    fixed: &Matrix<D>,
+    moving: &Matrix<D>,
+    run: &Run<D, Transform<D>>,
+    samples: usize,
+    seed: u64,
+) -> Result<Option<DMatrix<f64>>, Error>
+where
+    D: DimName + DimMin<D> + DimMin<D, Output = D> + DimSub<U1>,
+    UInt: Mul<<D as DimName>::Value>,
+    <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    DefaultAllocator: Allocator<f64, D, D>
+        + Allocator<(usize, usize), D>
+        + Allocator<f64, <D as DimSub<U1>>::Output>,
+{
+    if samples < 2 {
+        return Ok(None);
+    }
+    let rigid = rigid.clone().uncertainty(None);
+    let planes = planes(D::dim());
+    let size = parameters(D::dim(), rigid.scale);
+    let mut rng = XorShift::new(seed);
+    let mut differences = DMatrix::<f64>::zeros(size, samples);
+    for sample in 0..samples {
+        let rows = (0..moving.nrows())
+            .map(|_| rng.below(moving.nrows()))
+            .collect::<Vec<_>>();
+        let resampled = Matrix::<D>::from_fn(rows.len(), |i, j| moving[(rows[i], j)]);
+        let transform = rigid.register_from(fixed, &resampled, &run.transform)?.transform;
+        let rotation = &transform.rotation * run.transform.rotation.transpose();
+        let mut difference = DVector::<f64>::zeros(size);
+        for (k, &(a, b)) in planes.iter().enumerate() {
+            difference[k] = (rotation[(b, a)] - rotation[(a, b)]) / 2.;
+        }
+        for d in 0..D::dim() {
+            difference[planes.len() + d] = transform.translation[d] - run.transform.translation[d];
+        }
+        if rigid.scale {
+            difference[size - 1] =
+                transform.scale.unwrap_or(1.) - run.transform.scale.unwrap_or(1.);
+        }
+        differences.set_column(sample, &difference);
+    }
+    let mean = differences.column_mean();
+    for mut column in differences.column_iter_mut() {
+        column -= &mean;
+    }
+    Ok(Some(&differences * differences.transpose() / (samples - 1) as f64))
+}
+
+/// Returns the number of rigid parameters.
+fn parameters(dimension: usize, scale: bool) -> usize {
+    dimension * (dimension - 1) / 2 + dimension + if scale { 1 } else { 0 }
+}
+
+/// Returns the planes of the rotation parameters, each rotating from the first axis toward the
+/// second.
+fn planes(dimension: usize) -> Vec<(usize, usize)> {
+    if dimension == 3 {
+        vec![(1, 2), (2, 0), (0, 1)]
+    } else {
+        (0..dimension)
+            .flat_map(|a| (a + 1..dimension).map(move |b| (a, b)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Rotation2;
+    use utils;
+
+    #[test]
+    fn observed_information() {
+        let fixed = utils::random_matrix2(50);
+        let moving = fixed.clone() * Rotation2::new(0.1) + utils::random_matrix2(50) * 0.01;
+        let rigid = Rigid::new();
+        let run = rigid.register(&fixed, &moving).unwrap();
+        let covariance = rigid
+            .covariance(&fixed, &moving, &run, Uncertainty::ObservedInformation)
+            .unwrap()
+            .unwrap();
+        assert_eq!((3, 3), covariance.shape());
+        assert_relative_eq!(covariance, covariance.transpose(), epsilon = 1e-12);
+        for i in 0..3 {
+            assert!(covariance[(i, i)] > 0.);
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let fixed = utils::random_matrix2(50);
+        let moving = fixed.clone() * Rotation2::new(0.1) + utils::random_matrix2(50) * 0.01;
+        let rigid = Rigid::new();
+        assert!(rigid.register(&fixed, &moving).unwrap().covariance.is_none());
+        let rigid = rigid.uncertainty(Uncertainty::ObservedInformation);
+        let run = rigid.register(&fixed, &moving).unwrap();
+        let covariance = rigid
+            .covariance(&fixed, &moving, &run, Uncertainty::ObservedInformation)
+            .unwrap();
+        assert_eq!(covariance, run.covariance);
+        let uncertainty = Uncertainty::Bootstrap {
+            samples: 3,
+            seed: 42,
+        };
+        let run = rigid.uncertainty(uncertainty).register(&fixed, &moving).unwrap();
+        assert_eq!((3, 3), run.covariance.unwrap().shape());
+    }
+
+    #[test]
+    fn observed_information_collapsed() {
+        let fixed = utils::random_matrix2(10);
+        let rigid = Rigid::new();
+        let mut run = rigid.register(&fixed, &fixed).unwrap();
+        run.denormalized_sigma2 = 0.;
+        let uncertainty = Uncertainty::ObservedInformation;
+        assert!(rigid.covariance(&fixed, &fixed, &run, uncertainty).unwrap().is_none());
+        run.denormalized_sigma2 = f64::NAN;
+        assert!(rigid.covariance(&fixed, &fixed, &run, uncertainty).unwrap().is_none());
+    }
+
+    #[test]
+    fn bootstrap() {
+        let fixed = utils::random_matrix2(20);
+        let moving = fixed.clone() * Rotation2::new(0.1);
+        let rigid = Rigid::new().scale(true);
+        let run = rigid.register(&fixed, &moving).unwrap();
+        let uncertainty = Uncertainty::Bootstrap {
+            samples: 5,
+            seed: 42,
+        };
+        let a = rigid.covariance(&fixed, &moving, &run, uncertainty).unwrap().unwrap();
+        let b = rigid.covariance(&fixed, &moving, &run, uncertainty).unwrap().unwrap();
+        assert_eq!((4, 4), a.shape());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(vec![(0, 1)], planes(2));
+        assert_eq!(3, parameters(2, false));
+        assert_eq!(7, parameters(3, true));
+    }
+}
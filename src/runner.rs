@@ -1,17 +1,25 @@
 //! Run cpd algorithms.
 
-use {Matrix, Normalize, Registration, Rigid, UInt};
+use {Annealing, Matrix, Normalize, Registration, Rigid, UInt, utils, validate};
+use annealing::Schedule;
+use normalize::Parameters;
 use failure::Error;
 use gauss_transform::Transformer;
 use generic_array::ArrayLength;
-use nalgebra::DimName;
+use nalgebra::{DMatrix, DVector, DimName};
 use std::f64;
+use std::borrow::Cow;
+use std::fmt;
 use std::ops::Mul;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 const DEFAULT_ERROR_CHANGE_THRESHOLD: f64 = 1e-5;
 const DEFAULT_MAX_ITERATIONS: usize = 150;
 const DEFAULT_OUTLIER_WEIGHT: f64 = 0.1;
 const DEFAULT_SIGMA2_THRESHOLD: f64 = f64::EPSILON * 10.;
+const FULL_RESOLUTION: &[usize] = &[1];
 
 /// Generic interface for running cpd registration methods.
 ///
@@ -22,6 +30,9 @@ const DEFAULT_SIGMA2_THRESHOLD: f64 = f64::EPSILON * 10.;
 /// let runner = Runner::new().max_iterations(100); // etc
 /// ```
 ///
+/// A runner is `Clone` but not `Copy`, since it can hold a `Cancel` handle that is shared between
+/// clones.
+///
 /// Use methods like `rigid()` to specifiy the type of registration, and convert the builder into a
 /// method-specific builder:
 ///
@@ -29,18 +40,158 @@ const DEFAULT_SIGMA2_THRESHOLD: f64 = f64::EPSILON * 10.;
 /// use cpd::Runner;
 /// let runner = Runner::new().rigid();
 /// ```
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Runner {
+    annealing: Annealing,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cancel: Option<Cancel>,
+    divergence_tolerance: Option<f64>,
     error_change_threshold: f64,
+    levels: Vec<usize>,
+    max_duration: Option<Duration>,
     max_iterations: usize,
     normalize: Normalize,
-    outlier_weight: f64,
-    sigma2: Option<f64>,
+    on_divergence: OnDivergence,
+    pub(crate) outlier_weight: f64,
+    pub(crate) sigma2: Option<f64>,
     sigma2_threshold: f64,
 }
 
+/// What a run should do when it diverges.
+///
+/// The default is to return an error:
+///
+/// ```
+/// use cpd::runner::OnDivergence;
+/// assert_eq!(OnDivergence::Error, OnDivergence::default());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OnDivergence {
+    /// Stop and return a `Diverged` error.
+    Error,
+
+    /// Roll back to the last good state and stop, returning the run.
+    Rollback,
+}
+
+/// The ways in which a run can diverge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Divergence {
+    /// The error (negative log-likelihood) is NaN or infinite.
+    NonFiniteError,
+
+    /// Sigma2 is NaN or infinite.
+    NonFiniteSigma2,
+
+    /// The transform produced NaN or infinite points.
+    NonFiniteTransform,
+
+    /// The error increased by more than the divergence tolerance.
+    ErrorIncrease,
+}
+
+/// An error returned when a run diverges.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Registration diverged after {} iterations: {}", iterations, divergence)]
+pub struct Diverged {
+    /// How the run diverged.
+    pub divergence: Divergence,
+
+    /// The number of completed iterations at the level that diverged.
+    pub iterations: usize,
+}
+
+/// A fixed point set that has been prepared for registering many moving point sets.
+///
+/// Create a batch with `Runner::batch`.
+#[derive(Debug)]
+pub struct Batch<'a, D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    normalized: Cow<'a, Matrix<D>>,
+    parameters: Parameters<D>,
+    runner: &'a Runner,
+}
+
+/// A shareable handle that can be used to cancel a run from another thread.
+///
+/// Clones share the same underlying flag, so cancelling any clone cancels them all.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::runner::Cancel;
+/// let cancel = Cancel::new();
+/// let other = cancel.clone();
+/// other.cancel();
+/// assert!(cancel.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+/// Statistics for one level of a (possibly multi-resolution) run.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Level {
+    /// The error (negative log-likelihood) of the final state of this level, in normalized units.
+    ///
+    /// NaN if this level did not complete any iterations.
+    pub error: f64,
+
+    /// The number of fixed points used at this level.
+    pub fixed_points: usize,
+
+    /// The number of iterations at this level.
+    pub iterations: usize,
+
+    /// The number of moving points used at this level.
+    pub moving_points: usize,
+
+    /// The sigma2 at the end of this level, in normalized units.
+    pub sigma2: f64,
+
+    /// The decimation stride used at this level, where one is full resolution.
+    pub stride: usize,
+
+    /// Why this level stopped iterating.
+    pub termination: Termination,
+}
+
+/// The reason a run stopped iterating.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Termination {
+    /// The change in error dropped below the error change threshold.
+    Converged,
+
+    /// Sigma2 dropped below the sigma2 threshold.
+    Sigma2,
+
+    /// The maximum number of iterations was reached.
+    MaxIterations,
+
+    /// The maximum duration elapsed.
+    Timeout,
+
+    /// The run was cancelled via a `Cancel` handle.
+    Cancelled,
+
+    /// The run diverged and was rolled back to the last good state.
+    Diverged,
+}
+
 /// The result of a cpd run.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde",
+           serde(bound(serialize = "Matrix<D>: ::serde::Serialize, T: ::serde::Serialize",
+                       deserialize = "Matrix<D>: ::serde::Deserialize<'de>, \
+                                      T: ::serde::Deserialize<'de>")))]
 pub struct Run<D, T>
 where
     D: DimName,
@@ -48,12 +199,50 @@ where
     /// Did this run converge?
     pub converged: bool,
 
-    /// The number of iterations.
+    /// The covariance of the transform parameters, if requested.
+    ///
+    /// Only rigid registrations estimate a covariance, and only if `Rigid::uncertainty` is set.
+    /// See `rigid::Uncertainty` for the layout of the rigid parameters.
+    pub covariance: Option<DMatrix<f64>>,
+
+    /// The final sigma2, in the units of the input points.
+    ///
+    /// This is the variance of the Gaussian mixture, so its square root is an estimate of the
+    /// registration residual.
+    pub denormalized_sigma2: f64,
+
+    /// The error (negative log-likelihood) of the final state, in normalized units.
+    ///
+    /// This is the error of the returned transform and sigma2. It is only comparable between runs
+    /// on the same point sets with the same normalization, and is NaN if the last level did not
+    /// complete any iterations, e.g. because the run was cancelled.
+    pub error: f64,
+
+    /// The number of iterations, summed over all levels.
     pub iterations: usize,
 
+    /// Per-level statistics, coarsest first.
+    ///
+    /// Runs without a multi-resolution schedule have exactly one level.
+    pub levels: Vec<Level>,
+
     /// The moved points.
     pub moved: Matrix<D>,
 
+    /// The final correspondence weight of each moving point, i.e. the row sums of the
+    /// correspondence probabilities, if the last level ran at full resolution.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) p1: Option<DVector<f64>>,
+
+    /// The wall-clock duration of the run.
+    pub runtime: Duration,
+
+    /// The final sigma2, in normalized units.
+    pub sigma2: f64,
+
+    /// Why the run stopped iterating.
+    pub termination: Termination,
+
     /// The transform returned by the registration method.
     pub transform: T,
 }
@@ -71,6 +260,23 @@ impl Runner {
         Runner::default()
     }
 
+    /// Sets the divergence tolerance.
+    ///
+    /// If set, a run diverges when its error (negative log-likelihood) increases from one
+    /// iteration to the next by more than this fraction of the previous error. NaN or infinite
+    /// errors, sigma2s, and transforms are always treated as divergence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// let runner = Runner::new().divergence_tolerance(1e-3);
+    /// ```
+    pub fn divergence_tolerance<T: Into<Option<f64>>>(mut self, divergence_tolerance: T) -> Runner {
+        self.divergence_tolerance = divergence_tolerance.into();
+        self
+    }
+
     /// Sets the error change threshold.
     ///
     /// Make this lower if you want to get more precise.
@@ -86,6 +292,119 @@ impl Runner {
         self
     }
 
+    /// Sets the sigma2 annealing strategy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Annealing, Runner};
+    /// let runner = Runner::new().annealing(Annealing::Geometric {
+    ///     initial: Some(10.),
+    ///     factor: 0.9,
+    ///     floor: 0.01,
+    ///     combine: true,
+    /// });
+    /// ```
+    pub fn annealing(mut self, annealing: Annealing) -> Runner {
+        self.annealing = annealing;
+        self
+    }
+
+    /// Sets a cancellation handle.
+    ///
+    /// When the handle is cancelled, the run stops after the current iteration and returns the
+    /// transform and sigma2 with the lowest error seen so far in the current level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// use cpd::runner::Cancel;
+    /// let cancel = Cancel::new();
+    /// let runner = Runner::new().cancel(cancel.clone());
+    /// ```
+    pub fn cancel<T: Into<Option<Cancel>>>(mut self, cancel: T) -> Runner {
+        self.cancel = cancel.into();
+        self
+    }
+
+    /// Sets the multi-resolution schedule as a list of decimation strides, coarsest first.
+    ///
+    /// Each level registers every `stride`-th point of both point sets, using the result of the
+    /// previous level as its starting transform. The maximum number of iterations applies to each
+    /// level. An empty schedule registers only at full resolution, as does the default.
+    ///
+    /// The returned transform is always refined at full resolution, so a full-resolution level is
+    /// appended to schedules that do not end with one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// let runner = Runner::new().levels(vec![16, 4]);
+    /// assert_eq!(&[16, 4, 1], runner.strides());
+    /// ```
+    pub fn levels(mut self, mut levels: Vec<usize>) -> Runner {
+        if levels.last().map_or(false, |&stride| stride != 1) {
+            levels.push(1);
+        }
+        self.levels = levels;
+        self
+    }
+
+    /// Sets a geometric multi-resolution schedule.
+    ///
+    /// Creates `count` levels, each `factor` times denser than the one before, ending at full
+    /// resolution.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// let runner = Runner::new().pyramid(3, 4);
+    /// assert_eq!(Runner::new().levels(vec![16, 4, 1]).strides(), runner.strides());
+    /// ```
+    pub fn pyramid(self, count: usize, factor: usize) -> Runner {
+        let levels = (0..count)
+            .rev()
+            .map(|level| factor.pow(level as u32))
+            .collect();
+        self.levels(levels)
+    }
+
+    /// Returns the decimation strides of each level, coarsest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// assert_eq!(&[1], Runner::new().strides());
+    /// ```
+    pub fn strides(&self) -> &[usize] {
+        if self.levels.is_empty() {
+            FULL_RESOLUTION
+        } else {
+            &self.levels
+        }
+    }
+
+    /// Sets the maximum wall-clock duration of a run.
+    ///
+    /// When the duration elapses, the run stops after the current iteration and returns the
+    /// transform and sigma2 with the lowest error seen so far in the current level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// use std::time::Duration;
+    /// let runner = Runner::new().max_duration(Duration::from_secs(10));
+    /// ```
+    pub fn max_duration<T: Into<Option<Duration>>>(mut self, max_duration: T) -> Runner {
+        self.max_duration = max_duration.into();
+        self
+    }
+
     /// Sets the maximum number of iterations when running cpd.
     ///
     /// # Examples
@@ -112,6 +431,20 @@ impl Runner {
         self
     }
 
+    /// Sets what to do when a run diverges.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::Runner;
+    /// use cpd::runner::OnDivergence;
+    /// let runner = Runner::new().on_divergence(OnDivergence::Rollback);
+    /// ```
+    pub fn on_divergence(mut self, on_divergence: OnDivergence) -> Runner {
+        self.on_divergence = on_divergence;
+        self
+    }
+
     /// Sets the outlier weight.
     ///
     /// Does *not* check to see whether it is a valid value, yet.
@@ -129,7 +462,8 @@ impl Runner {
 
     /// Sets the initial sigma2.
     ///
-    /// If none, use the default sigma2 as calculated from the matrices.
+    /// If none, use the default sigma2 as calculated from the matrices. Like the values of an
+    /// `Annealing` schedule, the initial sigma2 is in normalized units.
     ///
     /// # Examples
     ///
@@ -169,6 +503,10 @@ impl Runner {
 
     /// Runs a `Registration`.
     ///
+    /// Both point sets are checked with `validate::point_set` before running. The registration
+    /// must be `Clone` so that it can be rolled back if the run diverges, but it is only cloned
+    /// when `on_divergence` is `OnDivergence::Rollback`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -184,10 +522,109 @@ impl Runner {
         &self,
         fixed: &Matrix<D>,
         moving: &Matrix<D>,
+        registration: R,
+    ) -> Result<Run<D, R::Transform>, Error>
+    where
+        R: Registration<D> + Into<<R as Registration<D>>::Transform> + Clone,
+        D: DimName,
+        UInt: Mul<<D as DimName>::Value>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
+        self.run_inner(fixed, None, moving, registration, None)
+    }
+
+    /// Runs a `Registration`, starting from an initial transform.
+    ///
+    /// The initial transform is in the units of the input points, and is normalized along with
+    /// them. Use `sigma2` to also provide an initial sigma2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Runner, Rigid, utils, U2};
+    /// use cpd::rigid::Transform;
+    ///
+    /// let runner = Runner::new();
+    /// let rigid = Rigid::new();
+    /// let registration = rigid.as_registration::<U2>().unwrap();
+    /// let matrix = utils::random_matrix2(10);
+    /// let initial = Transform::identity();
+    /// let run = runner.run_from(&matrix, &matrix, registration, &initial).unwrap();
+    /// ```
+    pub fn run_from<D, R>(
+        &self,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        registration: R,
+        initial: &R::Transform,
+    ) -> Result<Run<D, R::Transform>, Error>
+    where
+        R: Registration<D> + Into<<R as Registration<D>>::Transform> + Clone,
+        D: DimName,
+        UInt: Mul<<D as DimName>::Value>,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
+        self.run_inner(fixed, None, moving, registration, Some(initial))
+    }
+
+    /// Prepares a fixed point set for registering many moving point sets.
+    ///
+    /// The fixed point set is validated and normalized once, and then shared by every registration
+    /// in the batch. With `Normalize::SameScale` the shared fixed point set is still rescaled for
+    /// each moving point set, since the common scale depends on both.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Runner, Rigid, utils, U2};
+    /// let runner = Runner::new();
+    /// let rigid = Rigid::new();
+    /// let fixed = utils::random_matrix2(10);
+    /// let batch = runner.batch(&fixed).unwrap();
+    /// for _ in 0..3 {
+    ///     let moving = utils::random_matrix2(10);
+    ///     let registration = rigid.as_registration::<U2>().unwrap();
+    ///     let run = batch.run(&moving, registration).unwrap();
+    /// }
+    /// ```
+    pub fn batch<'a, D>(&'a self, fixed: &'a Matrix<D>) -> Result<Batch<'a, D>, Error>
+    where
+        D: DimName,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
+        validate::point_set(fixed)?;
+        let parameters = Parameters::new(fixed);
+        let normalized = if self.normalize == Normalize::None {
+            Cow::Borrowed(fixed)
+        } else {
+            let mut normalized = fixed.clone();
+            parameters.normalize(&mut normalized);
+            Cow::Owned(normalized)
+        };
+        Ok(Batch {
+            normalized: normalized,
+            parameters: parameters,
+            runner: self,
+        })
+    }
+
+    fn run_inner<D, R>(
+        &self,
+        fixed: &Matrix<D>,
+        prepared: Option<&Parameters<D>>,
+        moving: &Matrix<D>,
         mut registration: R,
+        initial: Option<&R::Transform>,
     ) -> Result<Run<D, R::Transform>, Error>
     where
-        R: Registration<D> + Into<<R as Registration<D>>::Transform>,
+        R: Registration<D> + Into<<R as Registration<D>>::Transform> + Clone,
         D: DimName,
         UInt: Mul<<D as DimName>::Value>,
         <D as DimName>::Value: Mul + Mul<UInt>,
@@ -195,47 +632,373 @@ impl Runner {
         <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
         <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
     {
-        let (fixed, mut moving, normalization) = self.normalize.normalize(fixed, moving);
+        let start = Instant::now();
+        validate::point_set(moving)?;
+        let (fixed, mut moving, normalization) = match prepared {
+            Some(fixed_parameters) => self.normalize
+                .normalize_prepared(fixed, fixed_parameters, moving),
+            None => {
+                validate::point_set(fixed)?;
+                self.normalize.normalize(fixed, moving)
+            }
+        };
+        if let Some(initial) = initial {
+            registration.set_transform(initial);
+            if let Some(ref normalization) = normalization {
+                registration.normalize(normalization);
+            }
+        }
+        let sigma2_scale = normalization
+            .as_ref()
+            .map_or(1., |normalization| normalization.fixed.scale.powi(2));
+        let mut schedule = Schedule::new(self.annealing);
+        let mut levels: Vec<Level> = Vec::new();
+        let mut p1 = None;
+        for &stride in self.strides() {
+            let fixed = utils::decimate(&fixed, stride);
+            let moving = utils::decimate(&moving, stride);
+            let estimate = match self.sigma2 {
+                Some(sigma2) if levels.is_empty() => sigma2,
+                _ => sigma2(&fixed, &registration.transform(&moving)),
+            };
+            let (level, level_p1) = self.run_level(
+                start,
+                stride,
+                &fixed,
+                &moving,
+                &mut registration,
+                &mut schedule,
+                estimate,
+            )?;
+            p1 = if stride == 1 { level_p1 } else { None };
+            levels.push(level);
+            match level.termination {
+                Termination::Timeout | Termination::Cancelled | Termination::Diverged => break,
+                _ => {}
+            }
+        }
+        let (error, sigma2, termination) = levels.last().map_or(
+            (f64::NAN, f64::NAN, Termination::MaxIterations),
+            |level| (level.error, level.sigma2, level.termination),
+        );
+        if let Some(normalization) = normalization {
+            registration.denormalize(&normalization);
+            normalization.moving.denormalize(moving.to_mut());
+        }
+        let moved = registration.transform(&moving);
+        Ok(Run {
+            converged: termination.is_converged(),
+            covariance: None,
+            denormalized_sigma2: sigma2 * sigma2_scale,
+            error: error,
+            iterations: levels.iter().map(|level| level.iterations).sum(),
+            levels: levels,
+            moved: moved,
+            p1: p1,
+            runtime: start.elapsed(),
+            sigma2: sigma2,
+            termination: termination,
+            transform: registration.into(),
+        })
+    }
+
+    fn run_level<D, R>(
+        &self,
+        start: Instant,
+        stride: usize,
+        fixed: &Matrix<D>,
+        moving: &Matrix<D>,
+        registration: &mut R,
+        schedule: &mut Schedule,
+        estimate: f64,
+    ) -> Result<(Level, Option<DVector<f64>>), Error>
+    where
+        R: Registration<D> + Clone,
+        D: DimName,
+        <D as DimName>::Value: Mul + Mul<UInt>,
+        <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+    {
         let mut error = 0.;
         let mut error_change = f64::MAX;
         let mut iterations = 0;
-        let mut sigma2 = self.sigma2.unwrap_or(sigma2(&fixed, &moving));
-        let mut moved = moving.as_ref().clone();
-        let transformer = Transformer::new(&fixed, self.outlier_weight)?;
-        while iterations < self.max_iterations && self.error_change_threshold < error_change
-            && self.sigma2_threshold < sigma2
-        {
+        let mut sigma2 = schedule.start(estimate);
+        let mut good = if self.on_divergence == OnDivergence::Rollback {
+            Some((registration.clone(), sigma2, error))
+        } else {
+            None
+        };
+        let mut best: Option<(R, f64, f64)> = None;
+        let mut moved = registration.transform(moving);
+        let transformer = Transformer::new(fixed, self.outlier_weight)?;
+        let termination = loop {
+            if let Some(termination) = self.termination(start, iterations, error_change, sigma2) {
+                break termination;
+            }
             let probabilities = transformer.probabilities(&moved, sigma2);
+            let divergence = self.error_divergence(iterations, error, probabilities.error);
+            if let Some(divergence) = divergence {
+                break self.diverged(
+                    divergence,
+                    iterations,
+                    registration,
+                    &mut sigma2,
+                    &mut error,
+                    good,
+                )?;
+            }
+            if self.on_divergence == OnDivergence::Rollback {
+                good = Some((registration.clone(), sigma2, probabilities.error));
+            }
+            if self.can_stop_early()
+                && best.as_ref().map_or(true, |&(_, _, best)| probabilities.error < best)
+            {
+                best = Some((registration.clone(), sigma2, probabilities.error));
+            }
             error_change = ((probabilities.error - error) / probabilities.error).abs();
             info!(
-                "iterations={}, error_change={}, sigma2={}",
-                iterations, error_change, sigma2
+                "stride={}, iterations={}, error_change={}, sigma2={}",
+                stride, iterations, error_change, sigma2
             );
             error = probabilities.error;
-            sigma2 = registration.iterate(&fixed, &moving, &probabilities);
-            moved = registration.transform(&moving);
+            let estimate = registration.iterate(fixed, moving, &probabilities);
+            sigma2 = schedule.step(estimate);
+            moved = registration.transform(moving);
+            let divergence = if !(estimate.is_finite() && sigma2.is_finite()) {
+                Some(Divergence::NonFiniteSigma2)
+            } else if moved.iter().any(|n| !n.is_finite()) {
+                Some(Divergence::NonFiniteTransform)
+            } else {
+                None
+            };
+            if let Some(divergence) = divergence {
+                break self.diverged(
+                    divergence,
+                    iterations,
+                    registration,
+                    &mut sigma2,
+                    &mut error,
+                    good,
+                )?;
+            }
             iterations += 1;
+        };
+        let (mut error, mut p1) = if iterations == 0 {
+            (f64::NAN, None)
+        } else {
+            let probabilities = transformer.probabilities(&registration.transform(moving), sigma2);
+            (probabilities.error, Some(probabilities.p1))
+        };
+        match termination {
+            Termination::Cancelled | Termination::Timeout => {
+                if let Some((best_registration, best_sigma2, best_error)) = best {
+                    if !(error <= best_error) {
+                        *registration = best_registration;
+                        sigma2 = best_sigma2;
+                        error = best_error;
+                        p1 = None;
+                    }
+                }
+            }
+            _ => {}
         }
-        if let Some(normalization) = normalization {
-            registration.denormalize(&normalization);
-            normalization.moving.denormalize(moving.to_mut());
-        }
-        moved = registration.transform(&moving);
-        Ok(Run {
-            converged: iterations < self.max_iterations,
+        let level = Level {
+            error: error,
+            fixed_points: fixed.nrows(),
             iterations: iterations,
-            moved: moved,
-            transform: registration.into(),
-        })
+            moving_points: moving.nrows(),
+            sigma2: sigma2,
+            stride: stride,
+            termination: termination,
+        };
+        Ok((level, p1))
+    }
+
+    fn error_divergence(&self, iterations: usize, previous: f64, error: f64) -> Option<Divergence> {
+        if !error.is_finite() {
+            Some(Divergence::NonFiniteError)
+        } else if iterations > 0
+            && self.divergence_tolerance
+                .map_or(false, |tolerance| error - previous > tolerance * previous.abs())
+        {
+            Some(Divergence::ErrorIncrease)
+        } else {
+            None
+        }
+    }
+
+    fn diverged<R>(
+        &self,
+        divergence: Divergence,
+        iterations: usize,
+        registration: &mut R,
+        sigma2: &mut f64,
+        error: &mut f64,
+        good: Option<(R, f64, f64)>,
+    ) -> Result<Termination, Diverged> {
+        warn!("iterations={}, divergence={}", iterations, divergence);
+        match (self.on_divergence, good) {
+            (OnDivergence::Rollback, Some((good_registration, good_sigma2, good_error))) => {
+                *registration = good_registration;
+                *sigma2 = good_sigma2;
+                *error = good_error;
+                Ok(Termination::Diverged)
+            }
+            _ => Err(Diverged {
+                divergence: divergence,
+                iterations: iterations,
+            }),
+        }
+    }
+
+    fn can_stop_early(&self) -> bool {
+        self.cancel.is_some() || self.max_duration.is_some()
+    }
+
+    pub(crate) fn termination(
+        &self,
+        start: Instant,
+        iterations: usize,
+        error_change: f64,
+        sigma2: f64,
+    ) -> Option<Termination> {
+        if error_change <= self.error_change_threshold {
+            Some(Termination::Converged)
+        } else if sigma2 <= self.sigma2_threshold {
+            Some(Termination::Sigma2)
+        } else if iterations >= self.max_iterations {
+            Some(Termination::MaxIterations)
+        } else if self.cancel.as_ref().map_or(false, |cancel| cancel.is_cancelled()) {
+            Some(Termination::Cancelled)
+        } else if self.max_duration
+            .map_or(false, |max_duration| start.elapsed() >= max_duration)
+        {
+            Some(Termination::Timeout)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, D> Batch<'a, D>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Runs a `Registration` of a moving point set against this batch's fixed point set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{Runner, Rigid, utils, U2};
+    /// let runner = Runner::new();
+    /// let fixed = utils::random_matrix2(10);
+    /// let batch = runner.batch(&fixed).unwrap();
+    /// let moving = utils::random_matrix2(10);
+    /// let rigid = Rigid::new();
+    /// let registration = rigid.as_registration::<U2>().unwrap();
+    /// let run = batch.run(&moving, registration).unwrap();
+    /// ```
+    pub fn run<R>(&self, moving: &Matrix<D>, registration: R) -> Result<Run<D, R::Transform>, Error>
+    where
+        R: Registration<D> + Into<<R as Registration<D>>::Transform> + Clone,
+        UInt: Mul<<D as DimName>::Value>,
+        <UInt as Mul<<D as DimName>::Value>>::Output: ArrayLength<f64>,
+        <<D as DimName>::Value as Mul>::Output: ArrayLength<f64>,
+    {
+        self.runner
+            .run_inner(&self.normalized, Some(&self.parameters), moving, registration, None)
+    }
+}
+
+impl Cancel {
+    /// Creates a new, uncancelled handle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::runner::Cancel;
+    /// let cancel = Cancel::new();
+    /// assert!(!cancel.is_cancelled());
+    /// ```
+    pub fn new() -> Cancel {
+        Cancel::default()
+    }
+
+    /// Cancels every run that uses this handle, or any of its clones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::runner::Cancel;
+    /// let cancel = Cancel::new();
+    /// cancel.cancel();
+    /// assert!(cancel.is_cancelled());
+    /// ```
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if this handle has been cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::runner::Cancel;
+    /// assert!(!Cancel::new().is_cancelled());
+    /// ```
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for OnDivergence {
+    fn default() -> OnDivergence {
+        OnDivergence::Error
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Divergence::NonFiniteError => write!(f, "non-finite error"),
+            Divergence::NonFiniteSigma2 => write!(f, "non-finite sigma2"),
+            Divergence::NonFiniteTransform => write!(f, "non-finite transform"),
+            Divergence::ErrorIncrease => write!(f, "error increased"),
+        }
+    }
+}
+
+impl Termination {
+    /// Returns true if this termination reason means the run converged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::runner::Termination;
+    /// assert!(Termination::Converged.is_converged());
+    /// assert!(!Termination::Timeout.is_converged());
+    /// ```
+    pub fn is_converged(&self) -> bool {
+        match *self {
+            Termination::Converged | Termination::Sigma2 => true,
+            _ => false,
+        }
     }
 }
 
 impl Default for Runner {
     fn default() -> Runner {
         Runner {
+            annealing: Annealing::default(),
+            cancel: None,
+            divergence_tolerance: None,
             error_change_threshold: DEFAULT_ERROR_CHANGE_THRESHOLD,
+            levels: Vec::new(),
+            max_duration: None,
             max_iterations: DEFAULT_MAX_ITERATIONS,
             normalize: Normalize::default(),
+            on_divergence: OnDivergence::default(),
             outlier_weight: DEFAULT_OUTLIER_WEIGHT,
             sigma2: None,
             sigma2_threshold: DEFAULT_SIGMA2_THRESHOLD,
@@ -274,11 +1037,273 @@ where
 
 #[cfg(test)]
 mod tests {
-    use utils;
+    use super::*;
+    use {Normalization, U2, utils};
+    use gauss_transform::Probabilities;
 
     #[test]
     fn sigma2() {
         let matrix = utils::matrix2_from_slice(&[1., 2., 3., 4.]);
         assert_relative_eq!(0.5, super::sigma2(&matrix, &matrix));
     }
+
+    #[test]
+    fn statistics() {
+        let fixed = utils::random_matrix2(10);
+        let moving = &fixed * 10. + utils::random_matrix2(10) * 1e-3;
+        let run = Runner::new()
+            .normalize(Normalize::Independent)
+            .rigid()
+            .scale(true)
+            .register(&fixed, &moving)
+            .unwrap();
+        assert!(run.error.is_finite());
+        assert!(run.sigma2 < 1e-4);
+        assert_eq!(run.levels[0].sigma2, run.sigma2);
+        let (_, _, normalization) = Normalize::Independent.normalize(&fixed, &moving);
+        assert_relative_eq!(
+            run.sigma2 * normalization.unwrap().fixed.scale.powi(2),
+            run.denormalized_sigma2
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_json;
+        let runner = Runner::new()
+            .max_iterations(10)
+            .cancel(Cancel::new())
+            .annealing(Annealing::Fixed(1.));
+        let json = serde_json::to_string(&runner).unwrap();
+        let runner: Runner = serde_json::from_str(&json).unwrap();
+        assert_eq!(10, runner.max_iterations);
+        assert_eq!(Annealing::Fixed(1.), runner.annealing);
+        assert!(runner.cancel.is_none());
+
+        let matrix = utils::random_matrix2(10);
+        let run = runner.rigid().register(&matrix, &matrix).unwrap();
+        let json = serde_json::to_string(&run).unwrap();
+        let other: Run<U2, ::rigid::Transform<U2>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(run.transform, other.transform);
+        assert_eq!(run.iterations, other.iterations);
+    }
+
+    #[test]
+    fn batch_matches_run() {
+        let fixed = utils::random_matrix2(10);
+        let moving = &fixed * 2.;
+        for &normalize in &[Normalize::SameScale, Normalize::Independent, Normalize::None] {
+            let runner = Runner::new().normalize(normalize);
+            let rigid = runner.clone().rigid().scale(true);
+            let registration = rigid.as_registration::<U2>().unwrap();
+            let run = runner.run(&fixed, &moving, registration.clone()).unwrap();
+            let batch = runner.batch(&fixed).unwrap();
+            let other = batch.run(&moving, registration).unwrap();
+            assert_eq!(run.iterations, other.iterations);
+            assert_relative_eq!(run.moved, other.moved, epsilon = 1e-10);
+        }
+    }
+
+    #[test]
+    fn levels() {
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let run = Runner::new()
+            .levels(vec![2, 1])
+            .rigid()
+            .register(&fixed, &fixed)
+            .unwrap();
+        assert_eq!(2, run.levels.len());
+        assert_eq!(2, run.levels[0].stride);
+        assert_eq!(2, run.levels[0].moving_points);
+        assert_eq!(1, run.levels[1].stride);
+        assert_eq!(4, run.levels[1].moving_points);
+        assert_eq!(
+            run.iterations,
+            run.levels[0].iterations + run.levels[1].iterations
+        );
+        assert_relative_eq!(fixed, run.moved, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn run_from() {
+        use nalgebra::{Rotation2, U2};
+        use rigid::Transform;
+
+        let fixed = utils::matrix2_from_slice(&[1., 1., 1., 2., 1., 2., 3., 1.]);
+        let rotation = Rotation2::new(0.5);
+        let moving = fixed.clone() * rotation;
+        let initial = Transform::<U2> {
+            rotation: *rotation.matrix(),
+            scale: None,
+            translation: ::Vector::<U2>::zeros(),
+        };
+        let rigid = Rigid::new();
+        let cold = rigid.register(&fixed, &moving).unwrap();
+        let warm = rigid.register_from(&fixed, &moving, &initial).unwrap();
+        assert!(warm.converged);
+        assert!(warm.iterations <= cold.iterations);
+        assert_relative_eq!(fixed, warm.moved, epsilon = 1e-4);
+        assert_relative_eq!(initial.rotation, warm.transform.rotation, epsilon = 1e-8);
+    }
+
+    #[derive(Clone, Debug)]
+    struct NanSigma2;
+
+    impl Registration<U2> for NanSigma2 {
+        type Transform = NanSigma2;
+
+        fn iterate(&mut self, _: &Matrix<U2>, _: &Matrix<U2>, _: &Probabilities<U2>) -> f64 {
+            f64::NAN
+        }
+
+        fn transform(&self, moving: &Matrix<U2>) -> Matrix<U2> {
+            moving.clone()
+        }
+
+        fn set_transform(&mut self, _: &NanSigma2) {}
+
+        fn normalize(&mut self, _: &Normalization<U2>) {}
+
+        fn denormalize(&mut self, _: &Normalization<U2>) {}
+    }
+
+    #[test]
+    fn diverged_error() {
+        let matrix = utils::random_matrix2(10);
+        let error = Runner::new()
+            .run(&matrix, &matrix, NanSigma2)
+            .unwrap_err();
+        assert_eq!(
+            Some(&Diverged {
+                divergence: Divergence::NonFiniteSigma2,
+                iterations: 0,
+            }),
+            error.downcast_ref()
+        );
+    }
+
+    #[test]
+    fn diverged_rollback() {
+        let matrix = utils::random_matrix2(10);
+        let run = Runner::new()
+            .on_divergence(OnDivergence::Rollback)
+            .run(&matrix, &matrix, NanSigma2)
+            .unwrap();
+        assert_eq!(Termination::Diverged, run.termination);
+        assert!(!run.converged);
+        assert!(run.levels[0].sigma2.is_finite());
+    }
+
+    #[derive(Clone, Debug)]
+    struct NanTransform;
+
+    impl Registration<U2> for NanTransform {
+        type Transform = NanTransform;
+
+        fn iterate(&mut self, _: &Matrix<U2>, _: &Matrix<U2>, _: &Probabilities<U2>) -> f64 {
+            1.
+        }
+
+        fn transform(&self, moving: &Matrix<U2>) -> Matrix<U2> {
+            moving.map(|_| f64::NAN)
+        }
+
+        fn set_transform(&mut self, _: &NanTransform) {}
+
+        fn normalize(&mut self, _: &Normalization<U2>) {}
+
+        fn denormalize(&mut self, _: &Normalization<U2>) {}
+    }
+
+    #[test]
+    fn diverged_rollback_first_iteration() {
+        let matrix = utils::random_matrix2(10);
+        let run = Runner::new()
+            .on_divergence(OnDivergence::Rollback)
+            .sigma2(1.)
+            .run(&matrix, &matrix, NanTransform)
+            .unwrap();
+        assert_eq!(Termination::Diverged, run.termination);
+        assert_eq!(0, run.iterations);
+        assert_eq!(1., run.sigma2);
+    }
+
+    #[test]
+    fn cancelled() {
+        let cancel = Cancel::new();
+        cancel.cancel();
+        let fixed = utils::random_matrix2(10);
+        let moving = utils::random_matrix2(10);
+        let run = Runner::new()
+            .cancel(cancel)
+            .rigid()
+            .register(&fixed, &moving)
+            .unwrap();
+        assert_eq!(Termination::Cancelled, run.termination);
+        assert_eq!(0, run.iterations);
+        assert!(run.error.is_nan());
+        assert!(!run.converged);
+    }
+
+    #[test]
+    fn timeout() {
+        let fixed = utils::random_matrix2(10);
+        let moving = utils::random_matrix2(10);
+        let run = Runner::new()
+            .max_duration(Duration::from_secs(0))
+            .rigid()
+            .register(&fixed, &moving)
+            .unwrap();
+        assert_eq!(Termination::Timeout, run.termination);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Drifting {
+        cancel: Cancel,
+        offset: f64,
+    }
+
+    impl Registration<U2> for Drifting {
+        type Transform = Drifting;
+
+        fn iterate(&mut self, _: &Matrix<U2>, _: &Matrix<U2>, _: &Probabilities<U2>) -> f64 {
+            self.offset += 1.;
+            if self.offset >= 3. {
+                self.cancel.cancel();
+            }
+            1.
+        }
+
+        fn transform(&self, moving: &Matrix<U2>) -> Matrix<U2> {
+            moving.add_scalar(self.offset)
+        }
+
+        fn set_transform(&mut self, transform: &Drifting) {
+            self.offset = transform.offset;
+        }
+
+        fn normalize(&mut self, _: &Normalization<U2>) {}
+
+        fn denormalize(&mut self, _: &Normalization<U2>) {}
+    }
+
+    #[test]
+    fn cancelled_returns_best() {
+        let cancel = Cancel::new();
+        let matrix = utils::random_matrix2(10);
+        let registration = Drifting {
+            cancel: cancel.clone(),
+            offset: 0.,
+        };
+        let run = Runner::new()
+            .cancel(cancel)
+            .sigma2(1.)
+            .run(&matrix, &matrix, registration)
+            .unwrap();
+        assert_eq!(Termination::Cancelled, run.termination);
+        assert_eq!(3, run.iterations);
+        assert_eq!(0., run.transform.offset);
+        assert_eq!(1., run.sigma2);
+    }
 }
@@ -0,0 +1,72 @@
+use {Matrix, UInt};
+use generic_array::ArrayLength;
+use nalgebra::{DMatrix, DimName};
+use std::ops::Mul;
+
+/// A trait for the transforms that are returned by registrations.
+///
+/// This lets transforms be applied, chained, and inverted without knowing which flavor of cpd
+/// produced them.
+pub trait PointTransform<D>: Sized
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    /// Applies this transform to a matrix of points.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{PointTransform, U2, rigid, utils};
+    /// let matrix = utils::random_matrix2(10);
+    /// let transform = rigid::Transform::<U2>::identity();
+    /// assert_eq!(matrix, transform.apply(&matrix));
+    /// ```
+    fn apply(&self, matrix: &Matrix<D>) -> Matrix<D>;
+
+    /// Returns a transform that applies `other` first and then `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{PointTransform, U2, Vector, rigid};
+    /// let mut transform = rigid::Transform::<U2>::identity();
+    /// transform.translation = Vector::<U2>::new(1., 2.);
+    /// let composed = transform.compose(&transform);
+    /// assert_eq!(Vector::<U2>::new(2., 4.), composed.translation);
+    /// ```
+    fn compose(&self, other: &Self) -> Self;
+
+    /// Returns the inverse of this transform, if it has one.
+    ///
+    /// If this transform maps moving points onto fixed points, its inverse maps fixed points onto
+    /// moving points. Rigid transforms only lack an inverse when their scale is zero or not finite,
+    /// so callers should handle `None` rather than assume it cannot happen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{PointTransform, U2, Vector, rigid};
+    /// let mut transform = rigid::Transform::<U2>::identity();
+    /// transform.translation = Vector::<U2>::new(1., 2.);
+    /// let inverse = transform.inverse().unwrap();
+    /// assert_eq!(Vector::<U2>::new(-1., -2.), inverse.translation);
+    /// ```
+    fn inverse(&self) -> Option<Self>;
+
+    /// Returns this transform as a `(D + 1)×(D + 1)` homogeneous matrix.
+    ///
+    /// Points are column vectors, so the translation is in the last column.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cpd::{PointTransform, U2, rigid};
+    /// let transform = rigid::Transform::<U2>::identity();
+    /// let homogeneous = transform.to_homogeneous();
+    /// assert_eq!((3, 3), homogeneous.shape());
+    /// assert_eq!(1., homogeneous[(2, 2)]);
+    /// ```
+    fn to_homogeneous(&self) -> DMatrix<f64>;
+}
@@ -1,7 +1,8 @@
-//! Basic utility functions for creating matrices.
+//! Basic utility functions for creating, reading, and writing matrices.
 
 use Matrix;
-use nalgebra::U2;
+use nalgebra::{DimName, U2, U3};
+use std::borrow::Cow;
 
 /// Creates a random matrix with two columns and configurable rows.
 ///
@@ -17,6 +18,20 @@ pub fn random_matrix2(nrows: usize) -> Matrix<U2> {
     Matrix::<U2>::new_random(nrows)
 }
 
+/// Creates a random matrix with three columns and configurable rows.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::utils;
+/// let matrix = utils::random_matrix3(10);
+/// assert_eq!(3, matrix.ncols());
+/// assert_eq!(10, matrix.nrows());
+/// ```
+pub fn random_matrix3(nrows: usize) -> Matrix<U3> {
+    Matrix::<U3>::new_random(nrows)
+}
+
 /// Creates a 2-column matrix from a slice, column-major.
 ///
 /// # Examples
@@ -33,6 +48,31 @@ pub fn matrix2_from_slice(slice: &[f64]) -> Matrix<U2> {
     Matrix::<U2>::from_iterator(slice.len() / 2, slice.iter().map(|&n| n))
 }
 
+/// Decimates a matrix by keeping every `stride`-th row.
+///
+/// A stride of zero or one returns the matrix unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::utils;
+/// let matrix = utils::matrix2_from_slice(&[1., 2., 3., 4., 5., 6.]);
+/// let decimated = utils::decimate(&matrix, 2);
+/// assert_eq!(2, decimated.nrows());
+/// assert_eq!(3., decimated[(1, 0)]);
+/// ```
+pub fn decimate<D>(matrix: &Matrix<D>, stride: usize) -> Cow<Matrix<D>>
+where
+    D: DimName,
+{
+    if stride <= 1 {
+        Cow::Borrowed(matrix)
+    } else {
+        let nrows = (matrix.nrows() + stride - 1) / stride;
+        Cow::Owned(Matrix::<D>::from_fn(nrows, |i, j| matrix[(i * stride, j)]))
+    }
+}
+
 #[cfg(feature = "las")]
 /// Read las data into nalgebra matrices.
 ///
@@ -48,14 +88,5 @@ pub fn matrix_from_las_path<P>(path: P) -> Result<Matrix<::nalgebra::U3>, ::fail
 where
     P: AsRef<::std::path::Path>,
 {
-    use las::Reader;
-    let mut reader = Reader::from_path(path)?;
-    let mut matrix = Matrix::<::nalgebra::U3>::zeros(reader.header().number_of_points() as usize);
-    for (i, point) in reader.points().enumerate() {
-        let point = point?;
-        matrix[(i, 0)] = point.x;
-        matrix[(i, 1)] = point.y;
-        matrix[(i, 2)] = point.z;
-    }
-    Ok(matrix)
+    ::las::LasReader::new().read_path(path).map(|las| las.points)
 }
@@ -0,0 +1,165 @@
+//! Validate point sets before registering them.
+//!
+//! Degenerate point sets, e.g. empty ones or ones where every point is identical, would otherwise
+//! cause divisions by zero during normalization and registration. `Runner::run` validates both
+//! point sets before doing anything else, but you can also validate point sets yourself:
+//!
+//! ```
+//! use cpd::{utils, validate};
+//! let matrix = utils::random_matrix2(10);
+//! validate::point_set(&matrix).unwrap();
+//! ```
+
+use {Matrix, UInt};
+use failure::Error;
+use generic_array::ArrayLength;
+use nalgebra::DimName;
+use normalize::Parameters;
+use std::ops::Mul;
+
+/// The spread, relative to the magnitude of the coordinates, below which the points are treated as
+/// identical.
+const SPREAD_TOLERANCE: f64 = 1e-12;
+
+/// An error returned if a point set has no points.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Point set is empty")]
+pub struct EmptyPointSet;
+
+/// An error returned if a point set has a NaN or infinite coordinate.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Non-finite value at row {}, column {}: {}", row, column, value)]
+pub struct NonFiniteValue {
+    /// The row of the first non-finite value.
+    pub row: usize,
+
+    /// The column of the first non-finite value.
+    pub column: usize,
+
+    /// The non-finite value.
+    pub value: f64,
+}
+
+/// An error returned if every point in a point set is identical, or nearly so.
+///
+/// Points are nearly identical if their spread is tiny relative to the magnitude of their
+/// coordinates, e.g. floating-point noise around a repeated value.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Point set has zero spread, all points are identical")]
+pub struct ZeroSpread;
+
+/// An error returned if a point set has fewer points than dimensions.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+#[fail(display = "Too few points for {} dimensions: {}", dimension, points)]
+pub struct TooFewPoints {
+    /// The number of points.
+    pub points: usize,
+
+    /// The dimension of the points.
+    pub dimension: usize,
+}
+
+/// Validates a point set.
+///
+/// Returns an error if the point set is empty, has fewer points than dimensions, has a NaN or
+/// infinite coordinate, or if all of its points are identical.
+///
+/// # Examples
+///
+/// ```
+/// use cpd::{utils, validate};
+/// let matrix = utils::matrix2_from_slice(&[1., 1., 2., 2.]);
+/// assert!(validate::point_set(&matrix).is_err());
+/// ```
+pub fn point_set<D>(matrix: &Matrix<D>) -> Result<(), Error>
+where
+    D: DimName,
+    <D as DimName>::Value: Mul + Mul<UInt>,
+    <<D as DimName>::Value as Mul<UInt>>::Output: ArrayLength<f64>,
+{
+    if matrix.nrows() == 0 {
+        return Err(EmptyPointSet.into());
+    }
+    if matrix.nrows() < D::dim() {
+        return Err(TooFewPoints {
+            points: matrix.nrows(),
+            dimension: D::dim(),
+        }.into());
+    }
+    for row in 0..matrix.nrows() {
+        for column in 0..D::dim() {
+            let value = matrix[(row, column)];
+            if !value.is_finite() {
+                return Err(NonFiniteValue {
+                    row: row,
+                    column: column,
+                    value: value,
+                }.into());
+            }
+        }
+    }
+    if Parameters::new(matrix).scale <= SPREAD_TOLERANCE * matrix.amax() {
+        return Err(ZeroSpread.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {U2, utils};
+    use std::f64;
+
+    fn error(matrix: &Matrix<U2>) -> Error {
+        point_set(matrix).unwrap_err()
+    }
+
+    #[test]
+    fn empty() {
+        let matrix = Matrix::<U2>::zeros(0);
+        assert_eq!(Some(&EmptyPointSet), error(&matrix).downcast_ref());
+    }
+
+    #[test]
+    fn too_few_points() {
+        let matrix = utils::matrix2_from_slice(&[1., 2.]);
+        assert_eq!(
+            Some(&TooFewPoints {
+                points: 1,
+                dimension: 2,
+            }),
+            error(&matrix).downcast_ref()
+        );
+    }
+
+    #[test]
+    fn non_finite_value() {
+        let matrix = utils::matrix2_from_slice(&[1., 2., f64::INFINITY, 4.]);
+        assert_eq!(
+            Some(&NonFiniteValue {
+                row: 0,
+                column: 1,
+                value: f64::INFINITY,
+            }),
+            error(&matrix).downcast_ref()
+        );
+    }
+
+    #[test]
+    fn zero_spread() {
+        let matrix = utils::matrix2_from_slice(&[1., 1., 2., 2.]);
+        assert_eq!(Some(&ZeroSpread), error(&matrix).downcast_ref());
+        let matrix = utils::matrix2_from_slice(&[1e3, 1e3 + 1e-13, 2e3, 2e3 - 1e-13]);
+        assert_eq!(Some(&ZeroSpread), error(&matrix).downcast_ref());
+        let matrix = utils::matrix2_from_slice(&[1e3, 1e3 + 1e-6, 2e3, 2e3]);
+        assert!(point_set(&matrix).is_ok());
+    }
+
+    #[test]
+    fn runner() {
+        use Rigid;
+        let fixed = utils::random_matrix2(10);
+        let moving = Matrix::<U2>::zeros(0);
+        assert!(Rigid::new().register(&fixed, &moving).is_err());
+    }
+}